@@ -0,0 +1,320 @@
+extern crate clap;
+extern crate classification;
+extern crate djvuxml;
+extern crate regex;
+extern crate serde_json;
+
+use clap::{App, AppSettings, ArgMatches, SubCommand};
+use classification::cli;
+use classification::evaluate::evaluate;
+use classification::{for_each_item, for_each_source, load_model};
+use djvuxml::alto::write_alto;
+use djvuxml::cache::{write_cached_book, BookSource};
+use djvuxml::context::{context_windows, Keywords};
+use djvuxml::dump::{dump_rich, dump_text_with, dump_words};
+use djvuxml::format::Format;
+use djvuxml::hocr::write_hocr;
+use djvuxml::numeric::{numeric_windows, NumberPattern, NumericOptions};
+use djvuxml::pagenum::label_pages;
+use djvuxml::plaintext::{process_text, PlainTextOptions};
+use djvuxml::running::mark_running_lines;
+use djvuxml::trim_book;
+use djvuxml::writer::write_djvu_xml;
+use regex::Regex;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::process::Command;
+
+/// Run one of the ``dump_*`` functions on the file named in ``matches``.
+fn dump<F>(matches: &ArgMatches, f: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce(BufReader<File>, &mut io::StdoutLock) -> io::Result<()>,
+{
+    let input = BufReader::new(File::open(matches.value_of("input").unwrap_or_default())?);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    f(input, &mut out)?;
+    Ok(())
+}
+
+fn run_numeric_windows(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let options = NumericOptions {
+        window: count(matches, "window")?,
+        pattern: NumberPattern::parse(matches.value_of("pattern").unwrap_or_default())?,
+    };
+    let stdout = io::stdout();
+    let mut out = output(matches, &stdout)?;
+    for_each_source(
+        matches.value_of("input_books").unwrap_or_default(),
+        |name, source| Ok(numeric_windows(name, source, &options, &mut out)?),
+    )?;
+    out.flush()?;
+    Ok(())
+}
+
+fn run_cache(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let text_options = PlainTextOptions {
+        page_delimiter: match matches.value_of("page_delimiter") {
+            Some(expr) => Some(Regex::new(expr)?),
+            None => None,
+        },
+        lines_per_page: count(matches, "lines_per_page")?,
+    };
+    let mut out = BufWriter::new(File::create(
+        matches.value_of("output").unwrap_or_default(),
+    )?);
+    for_each_item(
+        matches.value_of("input_books").unwrap_or_default(),
+        |name, mut reader, metadata| {
+            let mut book = match Format::detect(&mut reader) {
+                Format::Text => process_text(reader, &text_options)?,
+                format => format.process_book(reader)?,
+            };
+            book.metadata = metadata;
+            Ok(write_cached_book(&mut out, name, &book)?)
+        },
+    )?;
+    out.flush()?;
+    Ok(())
+}
+
+/// The score of every page, by book.
+type Scores = HashMap<String, Vec<Option<f64>>>;
+
+/// Page scores from the output of ``poetry classify``.
+fn load_scores(path: &str) -> Result<Scores, Box<dyn Error>> {
+    let mut scores = Scores::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: serde_json::Value = serde_json::from_str(&line)?;
+        let (book, page) = match (record["book"].as_str(), record["page"].as_u64()) {
+            (Some(book), Some(page)) => (book, page as usize),
+            _ => return Err(format!("{} has a line without a book and page.", path).into()),
+        };
+        let pages = scores.entry(book.to_owned()).or_default();
+        if pages.len() <= page {
+            pages.resize(page + 1, None);
+        }
+        pages[page] = record["score"].as_f64();
+    }
+    Ok(scores)
+}
+
+fn run_export(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let dir = Path::new(matches.value_of("output_dir").unwrap_or_default());
+    fs::create_dir_all(dir)?;
+    let valid_only = matches.is_present("valid_pages_only");
+    let format = matches.value_of("format").unwrap_or_default();
+    let all_scores = match matches.value_of("scores") {
+        Some(path) => load_scores(path)?,
+        None => Scores::new(),
+    };
+    for_each_source(
+        matches.value_of("input_books").unwrap_or_default(),
+        |name, source| {
+            let mut book = source.into_book()?;
+            mark_running_lines(&mut book);
+            label_pages(&mut book);
+            let mut scores = all_scores.get(name).cloned().unwrap_or_default();
+            if valid_only {
+                scores.resize(book.pages.len(), None);
+                let (pages, kept): (Vec<_>, Vec<_>) = book
+                    .pages
+                    .into_iter()
+                    .zip(scores)
+                    .filter(|(page, _)| page.valid())
+                    .unzip();
+                book.pages = pages;
+                scores = kept;
+            }
+            let suffix = match format {
+                "hocr" => "_hocr.html",
+                "alto" => "_alto.xml",
+                _ => "_djvu.xml",
+            };
+            let path = dir.join(format!("{}{}", trim_book(name), suffix));
+            let mut out = BufWriter::new(File::create(path)?);
+            match format {
+                "hocr" => write_hocr(&book, &scores, &mut out)?,
+                "alto" => write_alto(&book, &scores, &mut out)?,
+                _ => write_djvu_xml(&book, &mut out)?,
+            }
+            Ok(out.flush()?)
+        },
+    )
+}
+
+/// A count argument, e.g. a window size.
+fn count(matches: &ArgMatches, name: &str) -> Result<usize, Box<dyn Error>> {
+    let value = matches.value_of(name).unwrap_or_default();
+    value
+        .parse::<usize>()
+        .map_err(|_| format!("--{} should be a count, not {:?}.", name, value).into())
+}
+
+/// Where to write, from ``--output``; defaults to stdout.
+fn output<'a>(
+    matches: &ArgMatches,
+    stdout: &'a io::Stdout,
+) -> Result<Box<dyn Write + 'a>, Box<dyn Error>> {
+    Ok(match matches.value_of("output") {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(stdout.lock()),
+    })
+}
+
+fn run_kwic(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let keywords = Keywords::new(matches.values_of("words").into_iter().flatten());
+    let regex = match matches.value_of("regex") {
+        Some(expr) => Some(Regex::new(expr)?),
+        None => None,
+    };
+    let window = count(matches, "window")?;
+    let width = count(matches, "width")?;
+    let text = matches.value_of("format") == Some("text");
+    let stdout = io::stdout();
+    let mut out = output(matches, &stdout)?;
+    for_each_source(
+        matches.value_of("input_books").unwrap_or_default(),
+        |name, source| {
+            Ok(context_windows(
+                name,
+                source,
+                window,
+                |w| keywords.matches(w) || regex.as_ref().is_some_and(|r| r.is_match(w.trim())),
+                |found| {
+                    if text {
+                        writeln!(out, "{}", found.line(width))
+                    } else {
+                        serde_json::to_writer(&mut out, &found)?;
+                        writeln!(out)
+                    }
+                },
+            )?)
+        },
+    )?;
+    out.flush()?;
+    Ok(())
+}
+
+fn run_train(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let status = Command::new(matches.value_of("python").unwrap_or("python3"))
+        .arg(matches.value_of("script").unwrap_or_default())
+        .arg("--truth")
+        .arg(matches.value_of("truth").unwrap_or_default())
+        .arg("--output")
+        .arg(matches.value_of("output").unwrap_or_default())
+        .arg("--stopwords")
+        .arg(matches.value_of("stopwords").unwrap_or_default())
+        .status()?;
+    if !status.success() {
+        return Err(format!("training failed: {}", status).into());
+    }
+    Ok(())
+}
+
+fn run_evaluate(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let model = load_model(matches.value_of("model").unwrap_or_default())?;
+    let threshold = matches
+        .value_of("threshold")
+        .unwrap_or_default()
+        .parse::<f64>()
+        .map_err(|_| "--threshold should be a number.")?;
+    let evaluation = evaluate(
+        &model,
+        matches.value_of("truth").unwrap_or_default(),
+        threshold,
+    )?;
+    println!("{}", serde_json::to_string(&evaluation)?);
+    Ok(())
+}
+
+fn main() {
+    let matches = App::new("poetry")
+        .version("0.1")
+        .author("John Foley <jfoley@cs.umass.edu>")
+        .about("Find poetry in scanned books from the Internet Archive.")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("classify")
+                .about("Score every page of a zip of DjVu XML books with a model, as JSON lines.")
+                .args(&cli::classify_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("features")
+                .about("Extract the features of every page of a zip of DjVu XML books, as JSON lines.")
+                .args(&cli::feature_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("dump-rich")
+                .about("Print every parsing event of a DjVu XML, hOCR, ALTO or plain text file.")
+                .args(&cli::dump_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("dump-text")
+                .about("Print the text of a DjVu XML, hOCR, ALTO or plain text file in reading order, one line per line of the page and a blank line after each page. Every page is treated as prose.")
+                .args(&cli::dump_text_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("dump-words")
+                .about("Print the tokens of a DjVu XML, hOCR, ALTO or plain text file, one per line.")
+                .args(&cli::dump_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("cache")
+                .about("Parse a zip of DjVu XML, hOCR, ALTO or plain text books once, so other commands can read the cache instead.")
+                .args(&cli::cache_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Write every book back out as DjVu XML (e.g. after repairing coordinates or dropping pages), hOCR or ALTO.")
+                .args(&cli::export_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("numeric-windows")
+                .about("Print every number in a zip of DjVu XML books with the words around it, as JSON lines.")
+                .args(&cli::numeric_window_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("kwic")
+                .about("Print every use of some words in a zip of DjVu XML books with the words around it: a keyword-in-context concordance.")
+                .args(&cli::kwic_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("train")
+                .about("Train a model on labeled page features with train_random_forest.py.")
+                .args(&cli::train_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("evaluate")
+                .about("Measure a model against labeled page features: AUC, precision, recall and F1.")
+                .args(&cli::evaluate_args()),
+        )
+        .get_matches();
+
+    let result = match matches.subcommand() {
+        ("classify", Some(m)) => cli::run_classify(m),
+        ("features", Some(m)) => cli::run_features(m),
+        ("dump-rich", Some(m)) => dump(m, |input, out| dump_rich(input, out)),
+        ("dump-text", Some(m)) => dump(m, |mut input, out| {
+            dump_text_with(BookSource::Xml(&mut input), &cli::text_options(m), out)
+        }),
+        ("dump-words", Some(m)) => dump(m, |mut input, out| {
+            dump_words(BookSource::Xml(&mut input), out)
+        }),
+        ("cache", Some(m)) => run_cache(m),
+        ("export", Some(m)) => run_export(m),
+        ("numeric-windows", Some(m)) => run_numeric_windows(m),
+        ("kwic", Some(m)) => run_kwic(m),
+        ("train", Some(m)) => run_train(m),
+        ("evaluate", Some(m)) => run_evaluate(m),
+        _ => Ok(()),
+    };
+    cli::exit_on_error(result);
+}
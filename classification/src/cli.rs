@@ -0,0 +1,489 @@
+use clap::{Arg, ArgMatches};
+use djvuxml::cache::is_cache;
+use djvuxml::export::{Separator, TextOptions};
+use djvuxml::quality::Lexicon;
+use djvuxml::text::{Normalizer, UnicodeForm};
+use std::error::Error;
+use std::process;
+use stopwords::StopwordSet;
+use {load_model, run, Options};
+
+/// A single book file, as the first positional argument.
+fn input_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("input")
+        .value_name("FILE")
+        .required(true)
+        .help("A DjVu XML, hOCR, ALTO or plain text file.")
+}
+
+/// The books to read, from ``--books``.
+fn books_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("input_books")
+        .long("books")
+        .value_name("FILE")
+        .takes_value(true)
+        .required(true)
+        .help("A zip file or directory of DjVu XML, hOCR, ALTO or plain text books (with any Internet Archive _meta.xml files), or a cache of them written by \"poetry cache\".")
+}
+
+/// Where to write, from ``--output``.
+fn output_arg<'a, 'b>(help: &'b str) -> Arg<'a, 'b> {
+    Arg::with_name("output")
+        .long("output")
+        .value_name("FILE")
+        .takes_value(true)
+        .help(help)
+}
+
+/// How much context to keep around what was found, from ``--window``.
+fn window_arg<'a, 'b>(default: &'a str) -> Arg<'a, 'b> {
+    Arg::with_name("window")
+        .long("window")
+        .value_name("N")
+        .takes_value(true)
+        .default_value(default)
+        .help("How many words and page or line boundaries to keep on either side.")
+}
+
+/// Labeled page features, from ``--truth``.
+fn truth_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("truth")
+        .long("truth")
+        .value_name("FILE")
+        .takes_value(true)
+        .default_value("truth-data/truth.jsonl")
+        .help("Labeled page features, one JSON object per line.")
+}
+
+/// How page text is exported; read them back with ``text_options``.
+fn text_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("dehyphenate")
+            .long("dehyphenate")
+            .help("Rejoin words hyphenated across line breaks in the text of prose pages."),
+        Arg::with_name("normalize_whitespace")
+            .long("normalize-whitespace")
+            .help("Collapse whitespace inside words and drop empty words from page text."),
+        Arg::with_name("separator")
+            .long("separator")
+            .value_name("SEP")
+            .takes_value(true)
+            .possible_values(&["tab", "space"])
+            .default_value("tab")
+            .help("What to put between words in page text."),
+    ]
+}
+
+/// Arguments for turning a zip file of books into page features.
+pub fn feature_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    let mut args = vec![
+        books_arg(),
+        Arg::with_name("strip_running")
+            .long("strip-running")
+            .help("Exclude running headers and footers from features and page text."),
+    ];
+    args.extend(text_args());
+    args.extend(vec![
+        Arg::with_name("unicode")
+            .long("unicode")
+            .value_name("FORM")
+            .takes_value(true)
+            .possible_values(&["none", "nfc", "nfkc"])
+            .default_value("nfc")
+            .help("Normalize words to this Unicode form and fold long s, ligatures and curly quotes; \"none\" keeps the OCR text as is."),
+        Arg::with_name("stopwords")
+            .long("stopwords")
+            .value_name("LISTS")
+            .takes_value(true)
+            .help("Comma-separated stopword lists (inquery, french, german, latin, or a file with one word per line), or \"auto\" to pick by book language. Defaults to the list the model was trained with, or inquery."),
+        Arg::with_name("languages")
+            .long("languages")
+            .value_name("CODES")
+            .takes_value(true)
+            .help("Skip books not identified as a language in this comma-separated list (en, fr, de, la), including books in any other language."),
+        Arg::with_name("dictionary")
+            .long("dictionary")
+            .value_name("FILE")
+            .takes_value(true)
+            .help("A word list (one per line) used with each book's own vocabulary to estimate OCR quality."),
+        Arg::with_name("min_ocr_confidence")
+            .long("min-ocr-confidence")
+            .value_name("FRACTION")
+            .takes_value(true)
+            .default_value("0")
+            .help("Score pages whose OCR confidence estimate is below this as 0 and mark them low_quality."),
+    ]);
+    args
+}
+
+/// ``feature_args``, plus a model to score pages with and what to do with the scores.
+pub fn classify_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    let mut args = feature_args();
+    args.push(
+        Arg::with_name("model")
+            .long("model")
+            .value_name("FILE")
+            .takes_value(true)
+            .required(true)
+            .help("A model written by train_random_forest.py."),
+    );
+    args.push(
+        Arg::with_name("poems")
+            .long("poems")
+            .value_name("FILE")
+            .takes_value(true)
+            .help("Split poetry pages into individual poems and write them to this file."),
+    );
+    args.push(
+        Arg::with_name("poem_threshold")
+            .long("poem-threshold")
+            .value_name("SCORE")
+            .takes_value(true)
+            .default_value("0.5")
+            .help(
+                "Pages scoring at least this are treated as poetry by --poems and --dehyphenate.",
+            ),
+    );
+    args
+}
+
+/// Arguments for the ``dump-*`` commands: one book file.
+pub fn dump_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![input_arg()]
+}
+
+/// ``dump_args``, plus how to export the text.
+pub fn dump_text_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    let mut args = dump_args();
+    args.extend(text_args());
+    args
+}
+
+/// Arguments for parsing books once into a cache.
+pub fn cache_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        books_arg().help("A zip file or directory of DjVu XML, hOCR, ALTO or plain text books, with any Internet Archive _meta.xml files."),
+        Arg::with_name("page_delimiter")
+            .long("page-delimiter")
+            .value_name("EXPR")
+            .takes_value(true)
+            .help("In plain text, lines matching this expression start a new page and are dropped; form feeds always do."),
+        Arg::with_name("lines_per_page")
+            .long("lines-per-page")
+            .value_name("N")
+            .takes_value(true)
+            .default_value("54")
+            .help("In plain text, start a new page after this many lines, blank ones included."),
+        output_arg("Where to write the parsed books, one JSON object per line.")
+            .required(true)
+            .validator(|path| {
+                if is_cache(&path) {
+                    Ok(())
+                } else {
+                    Err("the cache should be named *.jsonl".to_owned())
+                }
+            }),
+    ]
+}
+
+/// Arguments for writing books back out.
+pub fn export_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        books_arg(),
+        Arg::with_name("output_dir")
+            .long("output-dir")
+            .value_name("DIR")
+            .takes_value(true)
+            .required(true)
+            .help("Where to write one file per book, named like the archive's."),
+        Arg::with_name("valid_pages_only")
+            .long("valid-pages-only")
+            .help("Drop blank pages and pages missing their dimensions or DPI."),
+        Arg::with_name("format")
+            .long("format")
+            .value_name("FORMAT")
+            .takes_value(true)
+            .possible_values(&["djvu", "hocr", "alto"])
+            .default_value("djvu"),
+        Arg::with_name("scores")
+            .long("scores")
+            .value_name("FILE")
+            .takes_value(true)
+            .help("Output of \"poetry classify\" on the same books; with --format hocr or alto, each page is annotated with its poetry score."),
+    ]
+}
+
+/// Arguments for finding numbers in books.
+pub fn numeric_window_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        books_arg(),
+        output_arg("Where to write the windows; defaults to stdout."),
+        window_arg("32"),
+        Arg::with_name("pattern")
+            .long("pattern")
+            .value_name("PATTERN")
+            .takes_value(true)
+            .default_value("digits")
+            .help("Which words are numbers: digits, roman, years, or regex:EXPR."),
+    ]
+}
+
+/// Arguments for a keyword-in-context concordance.
+pub fn kwic_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        books_arg(),
+        Arg::with_name("words")
+            .long("words")
+            .value_name("WORDS")
+            .takes_value(true)
+            .use_delimiter(true)
+            .required_unless("regex")
+            .help("Comma-separated keywords, matched without regard to case or punctuation."),
+        Arg::with_name("regex")
+            .long("regex")
+            .value_name("EXPR")
+            .takes_value(true)
+            .help("Also match words (without surrounding whitespace) against this expression."),
+        window_arg("8"),
+        Arg::with_name("format")
+            .long("format")
+            .value_name("FORMAT")
+            .takes_value(true)
+            .possible_values(&["jsonl", "text"])
+            .default_value("jsonl")
+            .help("JSON lines, or aligned concordance lines for reading."),
+        Arg::with_name("width")
+            .long("width")
+            .value_name("CHARS")
+            .takes_value(true)
+            .default_value("40")
+            .help("With --format text, how much of the text before each keyword to show."),
+        output_arg("Where to write the concordance; defaults to stdout."),
+    ]
+}
+
+/// Arguments for training a model with ``train_random_forest.py``.
+pub fn train_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        truth_arg(),
+        output_arg("Where to write the model.").default_value("forest.json"),
+        Arg::with_name("stopwords")
+            .long("stopwords")
+            .value_name("LISTS")
+            .takes_value(true)
+            .default_value("inquery")
+            .help("The --stopwords the truth features were extracted with, recorded in the model."),
+        Arg::with_name("script")
+            .long("script")
+            .value_name("FILE")
+            .takes_value(true)
+            .default_value("train_random_forest.py"),
+        Arg::with_name("python")
+            .long("python")
+            .value_name("COMMAND")
+            .takes_value(true)
+            .default_value("python3"),
+    ]
+}
+
+/// Arguments for measuring a model against labeled pages.
+pub fn evaluate_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("model")
+            .long("model")
+            .value_name("FILE")
+            .takes_value(true)
+            .required(true),
+        truth_arg(),
+        Arg::with_name("threshold")
+            .long("threshold")
+            .value_name("SCORE")
+            .takes_value(true)
+            .default_value("0.5"),
+    ]
+}
+
+/// Report an error from a command and exit with status 1, so scripts can tell it failed.
+pub fn exit_on_error(result: Result<(), Box<dyn Error>>) {
+    if let Err(e) = result {
+        eprintln!("Error! {:?}", e);
+        process::exit(1);
+    }
+}
+
+/// A numeric argument, or ``default`` if this command does not take it.
+fn number(matches: &ArgMatches, name: &str, default: f64) -> Result<f64, Box<dyn Error>> {
+    let value = match matches.value_of(name) {
+        Some(value) => value,
+        None => return Ok(default),
+    };
+    value.parse::<f64>().map_err(|_| {
+        format!(
+            "--{} should be a number, not {:?}.",
+            name.replace('_', "-"),
+            value
+        )
+        .into()
+    })
+}
+
+/// Read ``TextOptions`` from the arguments of ``text_args``.
+pub fn text_options(matches: &ArgMatches) -> TextOptions {
+    TextOptions {
+        separator: match matches.value_of("separator") {
+            Some("space") => Separator::Space,
+            _ => Separator::Tab,
+        },
+        dehyphenate: matches.is_present("dehyphenate"),
+        normalize_whitespace: matches.is_present("normalize_whitespace"),
+    }
+}
+
+/// Read ``Options`` from the arguments of ``feature_args`` or ``classify_args``.
+///
+/// ``trained_with`` is the stopword list the model expects, if there is one; we warn if the
+/// run uses another.
+fn options(matches: &ArgMatches, trained_with: Option<&str>) -> Result<Options, Box<dyn Error>> {
+    let default = trained_with.unwrap_or("inquery");
+    let stopwords = matches.value_of("stopwords").unwrap_or(default);
+    let auto_stopwords = stopwords == "auto";
+    let stopwords = StopwordSet::from_spec(if auto_stopwords { default } else { stopwords })?;
+    if let Some(trained_with) = trained_with {
+        if stopwords.name() != trained_with || auto_stopwords {
+            eprintln!(
+                "Warning: model was trained with stopwords {:?}, but using {:?}.",
+                trained_with,
+                if auto_stopwords {
+                    "auto"
+                } else {
+                    stopwords.name()
+                }
+            );
+        }
+    }
+
+    let mut dictionary = Lexicon::new();
+    if let Some(path) = matches.value_of("dictionary") {
+        dictionary.load(path)?;
+    }
+
+    Ok(Options {
+        strip_running: matches.is_present("strip_running"),
+        poems: matches.value_of("poems").map(|p| p.to_owned()),
+        poem_threshold: number(matches, "poem_threshold", 0.5)?,
+        text: text_options(matches),
+        unicode: match matches.value_of("unicode") {
+            Some("none") => Normalizer::none(),
+            Some("nfkc") => Normalizer {
+                form: UnicodeForm::Nfkc,
+                ..Normalizer::all()
+            },
+            _ => Normalizer::all(),
+        },
+        stopwords,
+        auto_stopwords,
+        languages: matches
+            .value_of("languages")
+            .map(|l| l.split(',').map(|c| c.trim().to_owned()).collect()),
+        dictionary,
+        min_ocr_confidence: number(matches, "min_ocr_confidence", 0.0)?,
+    })
+}
+
+/// Score every page of every book with a model; takes the arguments of ``classify_args``.
+pub fn run_classify(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let model = load_model(matches.value_of("model").unwrap_or_default())?;
+    let options = options(matches, Some(model.stopwords()))?;
+    run(
+        matches.value_of("input_books").unwrap_or_default(),
+        Some(&model),
+        &options,
+    )
+}
+
+/// Extract the features of every page without scoring them, e.g. to build training data;
+/// takes the arguments of ``feature_args``.
+pub fn run_features(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let options = options(matches, None)?;
+    run(
+        matches.value_of("input_books").unwrap_or_default(),
+        None,
+        &options,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::App;
+    use djvuxml::cache::BookSource;
+    use djvuxml::plaintext::{process_text, PlainTextOptions};
+    use djvuxml::types::Book;
+    use serde_json::Value;
+
+    const XML: &str = r#"<OBJECT height="3000" width="2000"><PARAM name="DPI" value="600"/>
+<LINE><WORD coords="100,200,300,150,195">ſhe</WORD><WORD coords="320,200,500,150,195">ſaid</WORD><WORD coords="520,200,700,150,195">ſo</WORD></LINE>
+</OBJECT>"#;
+
+    /// The output for every page of ``book`` with these arguments.
+    fn records(book: Book, args: &[&str]) -> Vec<Value> {
+        let matches = App::new("test")
+            .args(&feature_args())
+            .get_matches_from(["test", "--books", "books.zip"].iter().chain(args));
+        let options = options(&matches, None).unwrap();
+        let mut out = Vec::new();
+        ::process_book(
+            &mut out,
+            None::<&mut Vec<u8>>,
+            "book",
+            None,
+            &options,
+            BookSource::Parsed(book),
+        )
+        .unwrap();
+        out.split(|&b| b == b'\n')
+            .filter(|l| !l.is_empty())
+            .map(|l| serde_json::from_slice(l).unwrap())
+            .collect()
+    }
+
+    /// The features of the one page of ``XML`` with these arguments.
+    fn features(args: &[&str]) -> Value {
+        let book = djvuxml::process_book(XML.as_bytes()).unwrap();
+        records(book, args)[0]["features"].clone()
+    }
+
+    #[test]
+    fn unicode_option_reaches_features() {
+        // "she" and "so" are stopwords, but "ſhe" and "ſo" are not.
+        assert!(features(&[])["stopwords"].as_f64().unwrap() > 0.5);
+        assert_eq!(
+            0.0,
+            features(&["--unicode", "none"])["stopwords"]
+                .as_f64()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn contents_link_to_pages() {
+        let mut text = String::from("CONTENTS\n\n");
+        for (title, page) in &[("To Autumn", 2), ("Ode", 3), ("Lamia", 4), ("Isabella", 5)] {
+            text.push_str(&format!("{:.<30} {}\n", title, page));
+        }
+        for page in 2..6 {
+            text.push_str(&format!("\x0cPOEMS {}\n\nSeason of mists\n", page));
+        }
+        let book = process_text(text.as_bytes(), &PlainTextOptions::default()).unwrap();
+        let records = records(book, &[]);
+        let entries = records[0]["toc_entries"].as_array().unwrap();
+        assert_eq!(4, entries.len());
+        assert_eq!("To Autumn", entries[0]["title"]);
+        assert_eq!(1, entries[0]["target"]);
+        assert_eq!(
+            vec!["Ode"],
+            records[2]["toc_titles"].as_array().unwrap().clone()
+        );
+        assert!(records[1]["toc_entries"].as_array().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead};
+use PyForestModel;
+
+/// One labeled page from ``truth-data/truth.jsonl``.
+#[derive(Deserialize)]
+struct TruthPage {
+    label: String,
+    features: HashMap<String, f64>,
+}
+
+/// How well a model's scores agree with labeled pages.
+#[derive(Debug, Serialize)]
+pub struct Evaluation {
+    pub pages: usize,
+    pub poetry: usize,
+    pub threshold: f64,
+    /// Area under the ROC curve: the chance a poetry page outscores a non-poetry page.
+    pub auc: f64,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+/// Area under the ROC curve of ``(score, is_poetry)`` pairs; ties count half.
+pub fn auc(scored: &[(f64, bool)]) -> f64 {
+    let mut sorted = scored.to_vec();
+    sorted.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    let positives = sorted.iter().filter(|s| s.1).count();
+    let negatives = sorted.len() - positives;
+    if positives == 0 || negatives == 0 {
+        return 0.5;
+    }
+    // Sum the (average, for ties) ranks of the positives: the Mann-Whitney U statistic.
+    let mut rank_sum = 0.0;
+    let mut i = 0;
+    while i < sorted.len() {
+        let mut j = i;
+        while j < sorted.len() && sorted[j].0 == sorted[i].0 {
+            j += 1;
+        }
+        let rank = (i + j + 1) as f64 / 2.0;
+        rank_sum += rank * sorted[i..j].iter().filter(|s| s.1).count() as f64;
+        i = j;
+    }
+    let positives = positives as f64;
+    (rank_sum - positives * (positives + 1.0) / 2.0) / (positives * negatives as f64)
+}
+
+/// Summarize ``(score, is_poetry)`` pairs, counting scores of at least ``threshold`` as poetry.
+pub fn summarize(scored: &[(f64, bool)], threshold: f64) -> Evaluation {
+    let predicted = scored.iter().filter(|s| s.0 >= threshold).count();
+    let poetry = scored.iter().filter(|s| s.1).count();
+    let correct = scored.iter().filter(|s| s.1 && s.0 >= threshold).count();
+    let ratio = |n: usize, d: usize| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+    let precision = ratio(correct, predicted);
+    let recall = ratio(correct, poetry);
+    Evaluation {
+        pages: scored.len(),
+        poetry,
+        threshold,
+        auc: auc(scored),
+        precision,
+        recall,
+        f1: if precision + recall > 0.0 {
+            2.0 * precision * recall / (precision + recall)
+        } else {
+            0.0
+        },
+    }
+}
+
+/// Score every page of a truth file (as read by ``train_random_forest.py``) with a model.
+pub fn evaluate(
+    model: &PyForestModel,
+    truth_path: &str,
+    threshold: f64,
+) -> Result<Evaluation, Box<dyn Error>> {
+    let reader = io::BufReader::new(File::open(truth_path)?);
+    let mut scored = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let page: TruthPage = serde_json::from_str(&line)?;
+        scored.push((model.predict(&page.features), page.label == "POETRY"));
+    }
+    Ok(summarize(&scored, threshold))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn area_under_curve() {
+        assert_eq!(1.0, auc(&[(0.1, false), (0.2, false), (0.9, true)]));
+        assert_eq!(0.0, auc(&[(0.9, false), (0.2, true)]));
+        assert_eq!(0.5, auc(&[(0.5, false), (0.5, true)]));
+        assert_eq!(
+            0.75,
+            auc(&[(0.1, false), (0.6, true), (0.5, false), (0.4, true)])
+        );
+    }
+
+    #[test]
+    fn precision_and_recall() {
+        let e = summarize(&[(0.9, true), (0.8, false), (0.2, true), (0.1, false)], 0.5);
+        assert_eq!(4, e.pages);
+        assert_eq!(2, e.poetry);
+        assert_eq!(0.5, e.precision);
+        assert_eq!(0.5, e.recall);
+        assert_eq!(0.5, e.f1);
+    }
+}
@@ -0,0 +1,643 @@
+extern crate djvuxml;
+extern crate num_traits;
+extern crate serde;
+extern crate serde_json;
+extern crate stats;
+#[macro_use]
+extern crate serde_derive;
+extern crate clap;
+extern crate zip;
+
+use djvuxml::cache::{is_cache, BookSource, CacheReader};
+use djvuxml::export::{page_text, TextOptions};
+use djvuxml::lang::{identify_book_with, identify_page_with, LanguageGuess};
+use djvuxml::meta::{item_file, parse_meta, pick_books, BookMetadata, ItemFile};
+use djvuxml::pagenum::label_pages;
+use djvuxml::poems::{segment_poems, PoemRecord};
+use djvuxml::quality::{page_quality_with, Lexicon};
+use djvuxml::running::mark_running_lines;
+use djvuxml::text::{tokenize_with, Normalizer};
+use djvuxml::toc::{parse_toc, TocEntry};
+use djvuxml::types::{BookPage, Geometry, PageStatus};
+use num_traits::cast::ToPrimitive;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use stopwords::StopwordSet;
+
+pub mod cli;
+pub mod evaluate;
+pub mod inquery;
+mod rhyme;
+mod stanza;
+pub mod stopwords;
+
+struct StreamingStats {
+    limits: stats::MinMax<f64>,
+    info: stats::OnlineStats,
+    total: f64,
+}
+impl StreamingStats {
+    fn new() -> StreamingStats {
+        StreamingStats {
+            limits: stats::MinMax::new(),
+            info: stats::OnlineStats::new(),
+            total: 0.0,
+        }
+    }
+    fn push_if_empty(&mut self, x: f64) {
+        if self.is_empty() {
+            self.push(x);
+        }
+    }
+    fn is_empty(&self) -> bool {
+        self.info.len() == 0
+    }
+    fn push(&mut self, x: f64) {
+        self.limits.add(x);
+        self.info.add(x);
+        self.total += x;
+    }
+    fn total(&self) -> f64 {
+        self.total
+    }
+    fn count(&self) -> f64 {
+        self.info.len() as f64
+    }
+    fn max(&self) -> f64 {
+        *self.limits.max().unwrap_or(&0.0)
+    }
+    fn min(&self) -> f64 {
+        *self.limits.min().unwrap_or(&0.0)
+    }
+    fn mean(&self) -> f64 {
+        self.info.mean()
+    }
+    fn stddev(&self) -> f64 {
+        self.info.stddev()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PageFeatures {
+    book: String,
+    page: u32,
+    status: PageStatus,
+    /// The model's score, or ``None`` when only extracting features.
+    score: Option<f64>,
+    features: HashMap<String, f64>,
+    text: Option<String>,
+    header: Option<String>,
+    footer: Option<String>,
+    /// The page number printed on the page, so results can cite "p. 47".
+    printed_page: Option<String>,
+    language: Option<LanguageGuess>,
+    book_language: Option<LanguageGuess>,
+    /// The OCR was too poor to trust, so the page was scored 0 (see ``--min-ocr-confidence``).
+    low_quality: bool,
+    /// Features measured from made-up coordinates (see ``Geometry::Synthetic``), which say
+    /// more about our layout of plain text than about the book.
+    unreliable: Vec<String>,
+    /// The book's catalogue record, from its Internet Archive item.
+    metadata: Option<BookMetadata>,
+    /// The entries of this page, if it is a table of contents, each with the scan it points to.
+    toc_entries: Vec<TocEntry>,
+    /// Titles that a table of contents in the book lists as starting on this page.
+    toc_titles: Vec<String>,
+}
+impl PageFeatures {
+    fn new(
+        book: &str,
+        page: u32,
+        status: PageStatus,
+        features: HashMap<String, f64>,
+    ) -> PageFeatures {
+        PageFeatures {
+            book: book.to_owned(),
+            page,
+            status,
+            score: None,
+            features,
+            text: None,
+            header: None,
+            footer: None,
+            printed_page: None,
+            language: None,
+            book_language: None,
+            low_quality: false,
+            unreliable: Vec::new(),
+            metadata: None,
+            toc_entries: Vec::new(),
+            toc_titles: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum DecisionTreeNode {
+    Branch {
+        fid: usize,
+        threshold: f64,
+        lhs: Box<DecisionTreeNode>,
+        rhs: Box<DecisionTreeNode>,
+    },
+    Leaf {
+        leaf: [f64; 2],
+    },
+}
+/// A random forest trained by ``train_random_forest.py``.
+#[derive(Serialize, Deserialize)]
+pub struct PyForestModel {
+    feature_names: Vec<String>,
+    /// The stopword list the training features were extracted with; older models used InQuery.
+    #[serde(default)]
+    stopwords: Option<String>,
+    forest: Vec<Vec<DecisionTreeNode>>,
+}
+
+impl DecisionTreeNode {
+    fn predict(&self, features: &[f64]) -> (f64, f64) {
+        match *self {
+            DecisionTreeNode::Branch {
+                fid,
+                threshold,
+                ref lhs,
+                ref rhs,
+            } => {
+                if features[fid] <= threshold {
+                    lhs.predict(features)
+                } else {
+                    rhs.predict(features)
+                }
+            }
+            DecisionTreeNode::Leaf { leaf } => (leaf[0], leaf[1]),
+        }
+    }
+}
+
+impl PyForestModel {
+    /// The probability that a page with these features is poetry.
+    pub fn predict(&self, features: &HashMap<String, f64>) -> f64 {
+        let mut linear_features = vec![0.0; self.feature_names.len() + 1];
+        for (feature, value) in features {
+            if let Some(index) = self.feature_names.iter().position(|f| f == feature) {
+                linear_features[index] = *value
+            } else {
+                // unused features.
+            }
+        }
+
+        let mut score_sum = 0.0;
+        let mut score_total = 0.0;
+        for forest in self.forest.iter() {
+            let total = forest.len() as f64;
+            let mut yes = 0.0;
+            for tree in forest.iter() {
+                let (y1, y2) = tree.predict(&linear_features);
+                yes += y2 / (y1 + y2);
+            }
+            score_sum += yes;
+            score_total += total;
+        }
+
+        score_sum / score_total
+    }
+    /// The stopword list the model's training features were extracted with.
+    pub fn stopwords(&self) -> &str {
+        self.stopwords.as_ref().map_or("inquery", |s| s.as_str())
+    }
+}
+
+/// Settings that change how pages are turned into features.
+struct Options {
+    /// Leave running headers and footers out of the features and text.
+    strip_running: bool,
+    /// Where to write individual poems found on poetry pages, if anywhere.
+    poems: Option<String>,
+    /// Pages scoring at least this much are poetry when looking for poems or exporting text.
+    poem_threshold: f64,
+    /// How page text is written out.
+    text: TextOptions,
+    /// Cleanup applied to every word before features and text are computed.
+    unicode: Normalizer,
+    /// Words counted by the ``stopwords`` feature.
+    stopwords: StopwordSet,
+    /// Count the stopwords of each book's language instead, where we have a list for it.
+    auto_stopwords: bool,
+    /// Only process books identified as one of these languages, if given.
+    languages: Option<Vec<String>>,
+    /// Known words beyond each book's own vocabulary, for OCR quality.
+    dictionary: Lexicon,
+    /// Pages with a lower OCR confidence proxy are scored 0.
+    min_ocr_confidence: f64,
+}
+
+/// One poem found in a book, written to the ``--poems`` file.
+#[derive(Serialize)]
+struct PoemOutput<'a> {
+    book: &'a str,
+    metadata: Option<&'a BookMetadata>,
+    #[serde(flatten)]
+    poem: PoemRecord,
+}
+
+fn process_book<W: io::Write, P: io::Write>(
+    out: &mut W,
+    poems: Option<&mut P>,
+    path: &str,
+    model: Option<&PyForestModel>,
+    options: &Options,
+    source: BookSource,
+) -> Result<(), Box<dyn Error>> {
+    let mut book = source.into_book()?;
+    book.normalize_text(&options.unicode);
+    let book_language = identify_book_with(&book, &options.unicode);
+    if let Some(ref wanted) = options.languages {
+        let language = book_language.as_ref().map(|g| g.language.as_str());
+        if !language.is_some_and(|l| wanted.iter().any(|w| w == l)) {
+            eprintln!(
+                "Skipping {}: language is {}.",
+                path,
+                language.unwrap_or("unknown")
+            );
+            return Ok(());
+        }
+    }
+    let language_stopwords = if options.auto_stopwords {
+        book_language
+            .as_ref()
+            .and_then(|g| StopwordSet::for_language(&g.language))
+    } else {
+        None
+    };
+    let stopword_set = language_stopwords.as_ref().unwrap_or(&options.stopwords);
+    let vocabulary = Lexicon::from_book_with(&book, &options.unicode);
+    let known = |t: &str| {
+        vocabulary.contains(t) || options.dictionary.contains(t) || stopword_set.contains(t)
+    };
+    mark_running_lines(&mut book);
+    label_pages(&mut book);
+    let toc = parse_toc(&book);
+    let pages: Vec<BookPage> = if options.strip_running {
+        book.pages.iter().map(|p| p.without_running()).collect()
+    } else {
+        book.pages.clone()
+    };
+
+    let mut page_words_stats = StreamingStats::new();
+    let mut page_punct_stats = StreamingStats::new();
+    let mut punct_by_page = Vec::new();
+    let num_pages = pages.len();
+    for p in &pages {
+        let mut n_words = 0;
+        let mut p_count = 0;
+        for l in &p.lines {
+            n_words += l.len();
+            p_count += l
+                .iter()
+                .map(|bw| &bw.text)
+                .filter(|w| w.chars().any(|c| c.is_ascii_punctuation()))
+                .count();
+        }
+        punct_by_page.push(p_count);
+        page_words_stats.push(n_words as f64);
+        // Avoid division by zero:
+        page_punct_stats.push(fraction(p_count, n_words))
+    }
+
+    let mut poetry_pages = Vec::with_capacity(num_pages);
+    let avg_punct = fmax(1.0, page_punct_stats.mean());
+    let avg_words = fmax(1.0, page_words_stats.mean());
+
+    for (i, p) in pages.iter().enumerate() {
+        let mut features = HashMap::new();
+        features.insert("page_fraction".to_owned(), fraction(i, num_pages));
+        //insert_stats(&mut features, "," stats)
+
+        let mut total_letters = 0;
+        let mut letters_cap = 0;
+        let mut letters_digits = 0;
+        let mut letters_or_digits = 0;
+        let mut stopwords = 0;
+        let mut left_margin = StreamingStats::new();
+        let mut right_margin = StreamingStats::new();
+        let mut words_per_line = StreamingStats::new();
+        let mut cap_lines = StreamingStats::new();
+        let mut cap_words = StreamingStats::new();
+        let mut num_words = 0;
+        for l in &p.lines {
+            num_words += l.len();
+            words_per_line.push(l.len() as f64);
+            for bw in l {
+                let word = &bw.text;
+                stopwords += tokenize_with(&options.unicode, word)
+                    .iter()
+                    .filter(|t| stopword_set.contains(t))
+                    .count();
+                if word
+                    .chars()
+                    .next()
+                    .map(|c| c.is_uppercase())
+                    .unwrap_or(false)
+                {
+                    cap_words.push(1.0)
+                } else {
+                    cap_words.push(0.0)
+                }
+                for c in word.chars() {
+                    total_letters += 1;
+                    if c.is_uppercase() {
+                        letters_cap += 1;
+                    }
+                    if c.is_ascii_digit() {
+                        letters_digits += 1;
+                    } else if c.is_alphabetic() {
+                        letters_or_digits += 1;
+                    }
+                }
+            }
+            if l.is_empty() {
+                left_margin.push(0.5);
+                right_margin.push(0.5);
+                cap_lines.push(0.0);
+            } else {
+                let first = l[0].bbox();
+                let last = l.last().expect("Last word has coordinates!").bbox();
+                left_margin.push(fraction(first.left, p.width));
+                right_margin.push(fraction(last.right, p.width));
+                if let Some(true) = l[0].text.chars().next().map(|c| c.is_uppercase()) {
+                    cap_lines.push(1.0);
+                } else {
+                    cap_lines.push(0.0);
+                }
+            }
+        }
+
+        left_margin.push_if_empty(0.5);
+        right_margin.push_if_empty(0.5);
+        cap_lines.push_if_empty(0.0);
+        cap_words.push_if_empty(0.0);
+        words_per_line.push_if_empty(0.0);
+
+        features.insert(
+            "scaled_punct".to_owned(),
+            fraction(punct_by_page[i], avg_punct),
+        );
+        features.insert("scaled_len".to_owned(), fraction(num_words, avg_words));
+        features.insert(
+            "cap_letters".to_owned(),
+            fraction(letters_cap, total_letters),
+        );
+        features.insert(
+            "digits_letters".to_owned(),
+            fraction(letters_digits, total_letters),
+        );
+        features.insert(
+            "alphanum_letters".to_owned(),
+            fraction(letters_or_digits, total_letters),
+        );
+        features.insert("num_pages".to_owned(), num_pages as f64);
+        features.insert("page_fraction".to_owned(), fraction(i, num_pages));
+        features.insert("num_words".to_owned(), num_words as f64);
+        features.insert("stopwords".to_owned(), fraction(stopwords, num_words));
+        insert_stats(&mut features, "left_margin", &left_margin);
+        insert_stats(&mut features, "right_margin", &right_margin);
+        insert_stats(&mut features, "words_per_line", &words_per_line);
+        insert_stats(&mut features, "cap_lines", &cap_lines);
+        insert_stats(&mut features, "cap_words", &cap_words);
+        stanza::insert_features(&mut features, p);
+        rhyme::insert_features(&mut features, p, &options.unicode);
+        let quality = page_quality_with(p, &options.unicode, known);
+        features.insert("ocr_dictionary_hits".to_owned(), quality.dictionary_hits);
+        features.insert("ocr_garbage".to_owned(), quality.garbage_ratio);
+        features.insert("ocr_confidence".to_owned(), quality.mean_confidence);
+        features.insert("ocr_coord_anomalies".to_owned(), quality.coord_anomalies);
+
+        let mut output = PageFeatures::new(path, i as u32, p.status(), features);
+        output.low_quality = quality.mean_confidence < options.min_ocr_confidence;
+        if book.geometry == Geometry::Synthetic {
+            output.unreliable = geometry_features(&output.features);
+        }
+        let score = match model {
+            Some(_) if output.low_quality => Some(0.0),
+            Some(model) => Some(model.predict(&output.features)),
+            None => None,
+        };
+        let is_poetry = match score {
+            Some(s) => s >= options.poem_threshold,
+            None => false,
+        };
+        poetry_pages.push(is_poetry);
+        output.score = score;
+        output.text = Some(page_text(p, &options.text, is_poetry));
+        output.header = book.pages[i].header_text();
+        output.footer = book.pages[i].footer_text();
+        output.printed_page = p.label.map(|l| l.to_string());
+        output.language = identify_page_with(p, &options.unicode);
+        output.book_language = book_language.clone();
+        output.metadata = book.metadata.clone();
+        output.toc_entries = toc.iter().filter(|e| e.toc_page == i).cloned().collect();
+        output.toc_titles = toc
+            .iter()
+            .filter(|e| e.target == Some(i))
+            .map(|e| e.title.clone())
+            .collect();
+        writeln!(out, "{}", serde_json::to_string(&output)?)?;
+    }
+
+    if let Some(dest) = poems {
+        for poem in segment_poems(&book, &poetry_pages) {
+            let record = PoemOutput {
+                book: path,
+                metadata: book.metadata.as_ref(),
+                poem,
+            };
+            writeln!(dest, "{}", serde_json::to_string(&record)?)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Features that depend on where words are on the page, by prefix. Indentation levels and
+/// stanza breaks are left out: in plain text they come from leading spaces and blank lines.
+const GEOMETRY_FEATURES: &[&str] = &[
+    "left_margin_",
+    "right_margin_",
+    "line_inches_",
+    "indent_inches_",
+    "line_spacing_",
+    "centered_lines",
+    "ocr_coord_anomalies",
+];
+
+/// The names of the geometry features among ``features``, sorted; counts of lines are kept.
+fn geometry_features(features: &HashMap<String, f64>) -> Vec<String> {
+    let mut names: Vec<String> = features
+        .keys()
+        .filter(|name| GEOMETRY_FEATURES.iter().any(|p| name.starts_with(p)))
+        .filter(|name| !name.ends_with("_count"))
+        .cloned()
+        .collect();
+    names.sort();
+    names
+}
+
+fn fraction<A, B>(numerator: A, denominator: B) -> f64
+where
+    A: ToPrimitive,
+    B: ToPrimitive,
+{
+    let x = numerator.to_f64().expect("Fraction Numerator");
+    let y = fmax(1.0, denominator.to_f64().expect("Fraction Denominator"));
+    x / y
+}
+
+fn fmax(lhs: f64, rhs: f64) -> f64 {
+    if lhs > rhs {
+        lhs
+    } else {
+        rhs
+    }
+}
+
+fn insert_stats(map: &mut HashMap<String, f64>, name: &str, stats: &StreamingStats) {
+    map.insert(format!("{}_max", name), stats.max());
+    map.insert(format!("{}_min", name), stats.min());
+    map.insert(format!("{}_mean", name), stats.mean());
+    map.insert(format!("{}_stddev", name), stats.stddev());
+    map.insert(format!("{}_total", name), stats.total());
+    map.insert(format!("{}_count", name), stats.count());
+}
+
+/// The files of a zip, or of a directory searched recursively, by name.
+enum Items {
+    Zip(zip::ZipArchive<io::BufReader<File>>),
+    Dir(PathBuf, Vec<String>),
+}
+
+impl Items {
+    fn open(path: &str) -> Result<Items, Box<dyn Error>> {
+        if Path::new(path).is_dir() {
+            let mut names = Vec::new();
+            list_files(Path::new(path), "", &mut names)?;
+            names.sort();
+            Ok(Items::Dir(PathBuf::from(path), names))
+        } else {
+            let file = File::open(path)?;
+            Ok(Items::Zip(zip::ZipArchive::new(io::BufReader::new(file))?))
+        }
+    }
+    fn names(&mut self) -> Result<Vec<String>, Box<dyn Error>> {
+        match *self {
+            Items::Zip(ref mut zip) => (0..zip.len())
+                .map(|i| Ok(zip.by_index(i)?.name().to_owned()))
+                .collect(),
+            Items::Dir(_, ref names) => Ok(names.clone()),
+        }
+    }
+    fn read<T, F>(&mut self, index: usize, f: F) -> Result<T, Box<dyn Error>>
+    where
+        F: FnOnce(&mut dyn io::BufRead) -> Result<T, Box<dyn Error>>,
+    {
+        match *self {
+            Items::Zip(ref mut zip) => f(&mut io::BufReader::new(zip.by_index(index)?)),
+            Items::Dir(ref root, ref names) => {
+                f(&mut io::BufReader::new(File::open(root.join(&names[index]))?))
+            }
+        }
+    }
+}
+
+/// Add the paths of the files under ``dir`` to ``names``, with ``/`` between directories as
+/// in a zip.
+fn list_files(dir: &Path, prefix: &str, names: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            list_files(&entry.path(), &format!("{}/", name), names)?;
+        } else {
+            names.push(name);
+        }
+    }
+    Ok(())
+}
+
+/// Call ``f`` with the name and contents of every book in a zip file or a directory (e.g. of
+/// Internet Archive items), with the metadata of the ``_meta.xml`` next to it if there is one.
+///
+/// Metadata is read first, so it may come before or after its book in a zip. Only one book
+/// of each item is read (see ``pick_books``), and its other files (see ``ItemFile::Other``)
+/// are skipped.
+pub fn for_each_item<F>(path: &str, mut f: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(&str, &mut dyn io::BufRead, Option<BookMetadata>) -> Result<(), Box<dyn Error>>,
+{
+    let mut items = Items::open(path)?;
+    let names = items.names()?;
+    let mut metadata = HashMap::new();
+    for (i, name) in names.iter().enumerate() {
+        if let ItemFile::Meta(item) = item_file(name) {
+            let meta = items.read(i, |reader| Ok(parse_meta(reader)?))?;
+            metadata.insert(item.to_owned(), meta);
+        }
+    }
+    let n = names.len();
+    for i in pick_books(&names) {
+        let name = &names[i];
+        if let ItemFile::Book(item) = item_file(name) {
+            eprintln!("{}/{}: {}", i, n, name);
+            items.read(i, |reader| f(name, reader, metadata.get(item).cloned()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Call ``f`` with the name and contents of every book in a zip file or directory of DjVu XML,
+/// hOCR, ALTO or plain text (see ``for_each_item``), or with the name and parsed book of every
+/// book in a cache written by ``poetry cache``.
+pub fn for_each_source<F>(path: &str, mut f: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(&str, BookSource) -> Result<(), Box<dyn Error>>,
+{
+    if !is_cache(path) {
+        return for_each_item(path, |name, reader, metadata| match metadata {
+            Some(metadata) => f(name, BookSource::Item(reader, metadata)),
+            None => f(name, BookSource::Xml(reader)),
+        });
+    }
+    for (i, cached) in CacheReader::new(io::BufReader::new(File::open(path)?)).enumerate() {
+        let cached = cached?;
+        eprintln!("{}: {}", i, cached.name);
+        f(&cached.name, BookSource::Parsed(cached.book))?;
+    }
+    Ok(())
+}
+
+/// Write the features (and score, given a model) of every page of every book to stdout.
+fn run(
+    archive_path: &str,
+    model: Option<&PyForestModel>,
+    options: &Options,
+) -> Result<(), Box<dyn Error>> {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    let mut poems = match options.poems {
+        Some(ref path) => Some(io::BufWriter::new(File::create(path)?)),
+        None => None,
+    };
+    for_each_source(archive_path, |name, source| {
+        process_book(&mut out, poems.as_mut(), name, model, options, source)
+    })
+}
+
+/// Load a model written by ``train_random_forest.py``.
+pub fn load_model(model_path: &str) -> Result<PyForestModel, Box<dyn Error>> {
+    let fp = io::BufReader::new(File::open(model_path)?);
+    let forest: PyForestModel = serde_json::from_reader(fp)?;
+    Ok(forest)
+}
@@ -0,0 +1,154 @@
+use djvuxml::text::{tokenize_with, Normalizer};
+use djvuxml::types::BookPage;
+use std::collections::HashMap;
+use {fraction, insert_stats, StreamingStats};
+
+/// How many following lines to look at for a rhyme (covers couplets, ABAB and ABBA).
+const RHYME_WINDOW: usize = 3;
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y')
+}
+
+/// Lowercase letters only, with a few spellings that sound alike folded together.
+fn phonetic(word: &str) -> String {
+    let letters: String = word
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .collect();
+    letters
+        .replace("ph", "f")
+        .replace("ck", "k")
+        .replace("gh", "")
+        .replace("ee", "ea")
+        .replace("ie", "y")
+}
+
+/// Drop a silent final ``e`` ("stone", "alone") so it does not count as a vowel.
+fn without_silent_e(word: &str) -> &str {
+    let chars: Vec<char> = word.chars().collect();
+    let n = chars.len();
+    if n > 3 && chars[n - 1] == 'e' && !is_vowel(chars[n - 2]) && chars[n - 2] != 'l' {
+        &word[..word.len() - 1]
+    } else {
+        word
+    }
+}
+
+/// The part of a word that has to match for it to rhyme: its last vowel sound and anything
+/// after it ("night" and "light" share "it", "stone" and "alone" share "on").
+pub fn rhyme_key(word: &str) -> Option<String> {
+    let sound = phonetic(word);
+    let stem = without_silent_e(&sound);
+    let chars: Vec<char> = stem.chars().collect();
+    let last_vowel = chars.iter().rposition(|&c| is_vowel(c))?;
+    let mut start = last_vowel;
+    while start > 0 && is_vowel(chars[start - 1]) {
+        start -= 1;
+    }
+    // A lone trailing "y" (as in "melody") rhymes on the vowel alone.
+    if start == chars.len() - 1 && chars[start] == 'y' {
+        return Some("y".to_owned());
+    }
+    Some(chars[start..].iter().collect())
+}
+
+/// Estimate syllables by counting groups of vowels, ignoring a silent final ``e``.
+pub fn syllables(word: &str) -> usize {
+    let sound: String = word
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .collect();
+    if sound.is_empty() {
+        return 0;
+    }
+    let stem = without_silent_e(&sound);
+    let mut count = 0;
+    let mut in_vowel = false;
+    for c in stem.chars() {
+        let v = is_vowel(c);
+        if v && !in_vowel {
+            count += 1;
+        }
+        in_vowel = v;
+    }
+    count.max(1)
+}
+
+/// Linguistic signals of verse: end rhyme, regular syllable counts, and repeated lines.
+pub fn insert_features(
+    features: &mut HashMap<String, f64>,
+    page: &BookPage,
+    normalizer: &Normalizer,
+) {
+    let mut endings: Vec<(String, String)> = Vec::new();
+    let mut per_line = StreamingStats::new();
+    let mut line_counts: HashMap<String, usize> = HashMap::new();
+
+    for line in &page.lines {
+        let words: Vec<String> = line
+            .iter()
+            .flat_map(|w| tokenize_with(normalizer, &w.text))
+            .collect();
+        let last = match words.last() {
+            Some(last) => last,
+            None => continue,
+        };
+        if let Some(key) = rhyme_key(last) {
+            endings.push((last.clone(), key));
+        }
+        per_line.push(words.iter().map(|w| syllables(w)).sum::<usize>() as f64);
+        *line_counts.entry(words.join(" ")).or_insert(0) += 1;
+    }
+
+    let mut rhyming = 0;
+    for (i, (word, key)) in endings.iter().enumerate() {
+        let rhymes = endings
+            .iter()
+            .skip(i + 1)
+            .take(RHYME_WINDOW)
+            .any(|(w, k)| k == key && w != word);
+        if rhymes {
+            rhyming += 1;
+        }
+    }
+
+    let num_lines = per_line.count();
+    let repeated: usize = line_counts.values().filter(|&&n| n > 1).sum();
+    per_line.push_if_empty(0.0);
+
+    features.insert("rhyme_density".to_owned(), fraction(rhyming, endings.len()));
+    features.insert(
+        "syllable_variation".to_owned(),
+        fraction(per_line.stddev(), per_line.mean()),
+    );
+    features.insert("repeated_lines".to_owned(), fraction(repeated, num_lines));
+    insert_stats(features, "syllables_per_line", &per_line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rhymes() {
+        assert_eq!(rhyme_key("night"), rhyme_key("light"));
+        assert_eq!(rhyme_key("stone"), rhyme_key("alone"));
+        assert_eq!(rhyme_key("day"), rhyme_key("away"));
+        assert_eq!(rhyme_key("phone"), rhyme_key("fone"));
+        assert_ne!(rhyme_key("night"), rhyme_key("day"));
+        assert_eq!(None, rhyme_key("---"));
+    }
+
+    #[test]
+    fn syllable_counts() {
+        assert_eq!(1, syllables("stone"));
+        assert_eq!(2, syllables("alone"));
+        assert_eq!(3, syllables("beautiful"));
+        assert_eq!(2, syllables("table"));
+        assert_eq!(1, syllables("the"));
+        assert_eq!(0, syllables("47"));
+    }
+}
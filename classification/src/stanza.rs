@@ -0,0 +1,163 @@
+use djvuxml::geometry::{line_baseline, line_bbox, BBox};
+use djvuxml::types::BookPage;
+use std::collections::HashMap;
+use {fraction, insert_stats, StreamingStats};
+
+/// Left edges within this many inches of each other are the same indentation level.
+const INDENT_TOLERANCE_INCHES: f64 = 0.1;
+/// Lines whose center is this close (as a fraction of text width) to the text center are centered.
+const CENTERED_TOLERANCE: f64 = 0.02;
+/// Lines wider than this fraction of the text block are too long to tell if they are centered.
+const CENTERED_MAX_WIDTH: f64 = 0.8;
+/// Baseline spacing more than this multiple of the usual spacing is a stanza break.
+const STANZA_BREAK_SPACING: f64 = 1.6;
+
+/// Group sorted left edges (in inches) into indentation levels.
+fn indent_levels(mut lefts: Vec<f64>) -> Vec<usize> {
+    lefts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mut levels: Vec<usize> = Vec::new();
+    let mut previous: Option<f64> = None;
+    for x in lefts {
+        match previous {
+            Some(p) if x - p <= INDENT_TOLERANCE_INCHES => *levels.last_mut().unwrap() += 1,
+            _ => levels.push(1),
+        }
+        previous = Some(x);
+    }
+    levels
+}
+
+fn median(xs: &[f64]) -> f64 {
+    if xs.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = xs.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    sorted[sorted.len() / 2]
+}
+
+/// Features describing the shape of verse on a page: indentation, stanza breaks, line
+/// lengths in physical units, and centered lines.
+///
+/// Physical measurements need the page's DPI; without it those statistics are left at zero.
+pub fn insert_features(features: &mut HashMap<String, f64>, page: &BookPage) {
+    let ordered = page.in_reading_order();
+    let boxes: Vec<BBox> = ordered.lines.iter().filter_map(|l| line_bbox(l)).collect();
+    let text = ordered.text_bbox();
+
+    let mut line_inches = StreamingStats::new();
+    let mut indent_inches = StreamingStats::new();
+    let mut line_spacing = StreamingStats::new();
+    let mut centered = 0;
+    let mut lefts = Vec::new();
+    let mut stanza_breaks = 0;
+
+    if let Some(text) = text {
+        for b in &boxes {
+            if let Some(w) = page.to_inches(f64::from(b.width())) {
+                line_inches.push(w);
+            }
+            if let Some(x) = page.to_inches(f64::from(b.left - text.left)) {
+                indent_inches.push(x);
+                lefts.push(x);
+            }
+            let off_center = (b.center_x() - text.center_x()).abs();
+            let tolerance = f64::from(text.width()) * CENTERED_TOLERANCE;
+            if off_center <= tolerance
+                && f64::from(b.width()) < f64::from(text.width()) * CENTERED_MAX_WIDTH
+            {
+                centered += 1;
+            }
+        }
+
+        let baselines: Vec<f64> = ordered
+            .lines
+            .iter()
+            .filter_map(|l| line_baseline(l))
+            .map(f64::from)
+            .collect();
+        // Only count downward steps; a jump back up is a new column, not a gap.
+        let spacing: Vec<f64> = baselines
+            .windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .filter(|&d| d > 0.0)
+            .collect();
+        let usual = median(&spacing);
+        for d in &spacing {
+            if usual > 0.0 {
+                line_spacing.push(d / usual);
+                if d / usual > STANZA_BREAK_SPACING {
+                    stanza_breaks += 1;
+                }
+            }
+        }
+    }
+
+    let levels = indent_levels(lefts);
+    let main_level = levels.iter().cloned().max().unwrap_or(0);
+    let num_lines = boxes.len();
+
+    line_inches.push_if_empty(0.0);
+    indent_inches.push_if_empty(0.0);
+    line_spacing.push_if_empty(0.0);
+
+    features.insert("indent_levels".to_owned(), levels.len() as f64);
+    features.insert(
+        "indented_lines".to_owned(),
+        fraction(num_lines.saturating_sub(main_level), num_lines),
+    );
+    features.insert("centered_lines".to_owned(), fraction(centered, num_lines));
+    features.insert("stanza_breaks".to_owned(), stanza_breaks as f64);
+    insert_stats(features, "line_inches", &line_inches);
+    insert_stats(features, "indent_inches", &indent_inches);
+    insert_stats(features, "line_spacing", &line_spacing);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use djvuxml::plaintext::{process_text, PlainTextOptions};
+
+    /// Two stanzas set in monospace at 300 DPI, ten characters to the inch: a line indented
+    /// by a fifth of an inch, and a short line centered under the full ones.
+    const VERSE: &str =
+        "aaaaaaaaaaaaaaaaaaaa\n  bbbbbbbbbb\n\ncccccccccccccccccccc\n     dddddddddd\n";
+
+    fn features(page: &BookPage) -> HashMap<String, f64> {
+        let mut features = HashMap::new();
+        insert_features(&mut features, page);
+        features
+    }
+
+    #[test]
+    fn verse_shape() {
+        let book = process_text(VERSE.as_bytes(), &PlainTextOptions::default()).unwrap();
+        let features = features(&book.pages[0]);
+        assert_eq!(1.0, features["stanza_breaks"]);
+        assert_eq!(3.0, features["indent_levels"]);
+        assert_eq!(0.5, features["indented_lines"]);
+        assert_eq!(0.25, features["centered_lines"]);
+        assert_eq!(2.0, features["line_inches_max"]);
+        assert_eq!(0.5, features["indent_inches_max"]);
+        assert_eq!(2.0, features["line_spacing_max"]);
+    }
+
+    #[test]
+    fn empty_page() {
+        let features = features(&BookPage::new());
+        assert_eq!(0.0, features["stanza_breaks"]);
+        assert_eq!(0.0, features["indent_levels"]);
+        assert_eq!(0.0, features["indented_lines"]);
+        assert_eq!(0.0, features["centered_lines"]);
+        assert_eq!(0.0, features["line_inches_max"]);
+        assert_eq!(0.0, features["line_spacing_total"]);
+    }
+
+    #[test]
+    fn levels_and_medians() {
+        assert_eq!(vec![2, 1], indent_levels(vec![0.5, 0.0, 0.05]));
+        assert_eq!(Vec::<usize>::new(), indent_levels(Vec::new()));
+        assert_eq!(2.0, median(&[3.0, 1.0, 2.0]));
+        assert_eq!(0.0, median(&[]));
+    }
+}
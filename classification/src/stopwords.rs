@@ -0,0 +1,189 @@
+use djvuxml::text::Normalizer;
+use inquery;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Read};
+
+/// The names of the lists compiled into the binary.
+pub const BUILTIN: &[&str] = &["inquery", "french", "german", "latin"];
+
+/// A set of stopwords, counted by the ``stopwords`` feature.
+///
+/// Every set has a name, so that the list used to extract features can be recorded in the
+/// model and checked when the model is applied.
+#[derive(Debug, Clone)]
+pub struct StopwordSet {
+    name: String,
+    words: HashSet<String>,
+    /// Match the word before an apostrophe too, for languages that elide articles and
+    /// pronouns; English contractions like "it's" are not stopwords unless listed.
+    elision: bool,
+}
+
+impl StopwordSet {
+    fn new<'a, I: IntoIterator<Item = &'a str>>(name: &str, words: I) -> StopwordSet {
+        let normalizer = Normalizer::all();
+        StopwordSet {
+            name: name.to_owned(),
+            words: words
+                .into_iter()
+                .map(|w| normalizer.normalize(w.trim()).to_lowercase())
+                .filter(|w| !w.is_empty())
+                .collect(),
+            elision: false,
+        }
+    }
+
+    /// This set, also matching elided words like "l'homme" (see ``contains``).
+    fn with_elision(mut self) -> StopwordSet {
+        self.elision = true;
+        self
+    }
+
+    /// Read a list with one word per line; blank lines and lines starting with ``#`` are skipped.
+    pub fn parse(name: &str, text: &str) -> StopwordSet {
+        StopwordSet::new(
+            name,
+            text.lines()
+                .map(|l| l.trim())
+                .filter(|l| !l.starts_with('#')),
+        )
+    }
+
+    /// One of the lists in ``BUILTIN``.
+    pub fn builtin(name: &str) -> Option<StopwordSet> {
+        match name {
+            "inquery" => Some(StopwordSet::new(name, inquery::WORDS.iter().cloned())),
+            "french" => Some(
+                StopwordSet::parse(name, include_str!("../stopwords/french.txt")).with_elision(),
+            ),
+            "german" => Some(StopwordSet::parse(
+                name,
+                include_str!("../stopwords/german.txt"),
+            )),
+            "latin" => Some(
+                StopwordSet::parse(name, include_str!("../stopwords/latin.txt")).with_elision(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// The built-in list for an ISO 639-1 language code, as returned by ``djvuxml::lang``.
+    pub fn for_language(code: &str) -> Option<StopwordSet> {
+        match code {
+            "en" => StopwordSet::builtin("inquery"),
+            "fr" => StopwordSet::builtin("french"),
+            "de" => StopwordSet::builtin("german"),
+            "la" => StopwordSet::builtin("latin"),
+            _ => None,
+        }
+    }
+
+    /// Load a list from a file, named after its path.
+    pub fn load(path: &str) -> io::Result<StopwordSet> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        Ok(StopwordSet::parse(path, &text))
+    }
+
+    /// Resolve a comma-separated list of built-in names and file paths ("inquery,latin")
+    /// into one set.
+    pub fn from_spec(spec: &str) -> io::Result<StopwordSet> {
+        let mut sets = Vec::new();
+        for part in spec.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            sets.push(match StopwordSet::builtin(part) {
+                Some(set) => set,
+                None => StopwordSet::load(part)?,
+            });
+        }
+        if sets.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "empty stopword list",
+            ));
+        }
+        Ok(StopwordSet::union(&sets))
+    }
+
+    /// All the words of several sets, named after all of them.
+    pub fn union(sets: &[StopwordSet]) -> StopwordSet {
+        StopwordSet {
+            name: sets
+                .iter()
+                .map(|s| s.name.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+            words: sets.iter().flat_map(|s| s.words.iter().cloned()).collect(),
+            elision: sets.iter().any(|s| s.elision),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Is this lowercase token a stopword? In the French and Latin lists an elided article or
+    /// pronoun counts too, so "l'homme" and "qu'il" match "l" and "qu".
+    pub fn contains(&self, token: &str) -> bool {
+        if self.words.contains(token) {
+            return true;
+        }
+        if !self.elision {
+            return false;
+        }
+        match token.find('\'') {
+            Some(i) if i > 0 => self.words.contains(&token[..i]),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtins() {
+        for name in BUILTIN {
+            let set = StopwordSet::builtin(name).unwrap();
+            assert_eq!(*name, set.name());
+            assert!(!set.is_empty());
+        }
+        let inquery = StopwordSet::builtin("inquery").unwrap();
+        assert_eq!(inquery::WORDS.len(), inquery.len());
+        assert!(inquery.contains("doesn't"));
+        // The forest was trained without counting contractions of listed words.
+        assert!(!inquery.contains("it's"));
+        assert!(!inquery.contains("we'll"));
+        assert!(!inquery.contains("poetry"));
+        assert!(StopwordSet::builtin("klingon").is_none());
+        for code in djvuxml::lang::languages() {
+            assert!(StopwordSet::for_language(code).is_some());
+        }
+    }
+
+    #[test]
+    fn parse_and_combine() {
+        let custom = StopwordSet::parse("custom", "# archaic\nThou\n\n  thee \n");
+        assert_eq!(2, custom.len());
+        assert!(custom.contains("thou"));
+
+        let latin = StopwordSet::builtin("latin").unwrap();
+        let both = StopwordSet::union(&[custom, latin]);
+        assert_eq!("custom,latin", both.name());
+        assert!(both.contains("thee") && both.contains("atque"));
+
+        let french = StopwordSet::from_spec("french").unwrap();
+        assert!(french.contains("l'homme"));
+        assert!(french.contains("étoit"));
+        assert!(StopwordSet::from_spec(" , ").is_err());
+    }
+}
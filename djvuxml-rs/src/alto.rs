@@ -0,0 +1,376 @@
+use coords::CoordsRepair;
+use geometry::{line_baseline, line_bbox, BBox};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use std::io::{self, BufRead, Write};
+use types::{Book, BookPage, RichDjVu, WordCoords};
+use writer::escape;
+
+/// Scores are written with this many decimals.
+const SCORE_DIGITS: usize = 4;
+
+/// The ``HPOS``, ``VPOS``, ``WIDTH`` and ``HEIGHT`` attributes of a box.
+fn position(b: &BBox) -> String {
+    format!(
+        "HPOS=\"{}\" VPOS=\"{}\" WIDTH=\"{}\" HEIGHT=\"{}\"",
+        b.left,
+        b.top,
+        b.width(),
+        b.height()
+    )
+}
+
+fn write_page<W: Write>(
+    page: &BookPage,
+    number: usize,
+    scored: bool,
+    out: &mut W,
+) -> io::Result<()> {
+    let id = format!("P{}", number + 1);
+    write!(
+        out,
+        "<Page ID=\"{}\" PHYSICAL_IMG_NR=\"{}\" WIDTH=\"{}\" HEIGHT=\"{}\"",
+        id,
+        number + 1,
+        page.width,
+        page.height
+    )?;
+    if let Some(label) = page.label {
+        write!(out, " PRINTED_IMG_NR=\"{}\"", label)?;
+    }
+    if scored {
+        write!(out, " TAGREFS=\"{}_POETRY\"", id)?;
+    }
+    writeln!(out, ">")?;
+    let page_box = BBox::new(0, 0, page.width, page.height);
+    writeln!(out, "<PrintSpace {}>", position(&page_box))?;
+
+    let lines: Vec<(usize, BBox)> = page
+        .lines
+        .iter()
+        .enumerate()
+        .filter_map(|(l, line)| line_bbox(line).map(|b| (l, b)))
+        .collect();
+    if let Some(block) = lines
+        .iter()
+        .map(|&(_, b)| b)
+        .fold(None, |acc, b| match acc {
+            None => Some(b),
+            Some(a) => Some(b.union(&a)),
+        })
+    {
+        writeln!(out, "<TextBlock ID=\"{}_B1\" {}>", id, position(&block))?;
+        for &(l, bbox) in &lines {
+            let line = &page.lines[l];
+            write!(
+                out,
+                "<TextLine ID=\"{}_L{}\" {}",
+                id,
+                l + 1,
+                position(&bbox)
+            )?;
+            if let Some(base) = line_baseline(line) {
+                write!(out, " BASELINE=\"{}\"", base)?;
+            }
+            write!(out, ">")?;
+            for (w, word) in line.iter().enumerate() {
+                if w > 0 {
+                    write!(out, "<SP/>")?;
+                }
+                write!(
+                    out,
+                    "<String ID=\"{}_L{}_S{}\" CONTENT=\"{}\" {}/>",
+                    id,
+                    l + 1,
+                    w + 1,
+                    escape(&word.text),
+                    position(&word.bbox())
+                )?;
+            }
+            writeln!(out, "</TextLine>")?;
+        }
+        writeln!(out, "</TextBlock>")?;
+    }
+    writeln!(out, "</PrintSpace>\n</Page>")
+}
+
+/// Write a book as one ALTO 4 document, for OCR viewers and digital-library tools.
+///
+/// Each page becomes a ``Page`` holding one ``TextBlock`` of all its lines, each line a
+/// ``TextLine`` with its baseline, and each word a ``String``; positions are in pixels and
+/// empty lines are left out. A printed page number found by ``pagenum::label_pages`` becomes
+/// ``PRINTED_IMG_NR``. ``scores`` holds a poetry score per page: each one is written as an
+/// ``OtherTag`` of ``TYPE="poetry-score"`` with the score as its ``LABEL``, referenced from
+/// its page's ``TAGREFS``; pages past its end, or with ``None``, get none.
+pub fn write_alto<W: Write>(book: &Book, scores: &[Option<f64>], out: &mut W) -> io::Result<()> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        out,
+        "<alto xmlns=\"http://www.loc.gov/standards/alto/ns-v4#\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:schemaLocation=\"http://www.loc.gov/standards/alto/ns-v4# http://www.loc.gov/standards/alto/v4/alto-4-2.xsd\">"
+    )?;
+    writeln!(
+        out,
+        "<Description>\n<MeasurementUnit>pixel</MeasurementUnit>\n</Description>"
+    )?;
+    let scored: Vec<(usize, f64)> = scores
+        .iter()
+        .take(book.pages.len())
+        .enumerate()
+        .filter_map(|(i, s)| s.map(|s| (i, s)))
+        .collect();
+    if !scored.is_empty() {
+        writeln!(out, "<Tags>")?;
+        for &(i, score) in &scored {
+            writeln!(
+                out,
+                "<OtherTag ID=\"P{}_POETRY\" TYPE=\"poetry-score\" LABEL=\"{:.*}\"/>",
+                i + 1,
+                SCORE_DIGITS,
+                score
+            )?;
+        }
+        writeln!(out, "</Tags>")?;
+    }
+    writeln!(out, "<Layout>")?;
+    for (i, page) in book.pages.iter().enumerate() {
+        let has_score = scores.get(i).map(|s| s.is_some()).unwrap_or(false);
+        write_page(page, i, has_score, out)?;
+    }
+    writeln!(out, "</Layout>\n</alto>")
+}
+
+/// The element name without its namespace prefix, e.g. ``String`` for ``alto:String``.
+fn local_name(name: &[u8]) -> &[u8] {
+    match name.iter().rposition(|&b| b == b':') {
+        Some(i) => &name[i + 1..],
+        None => name,
+    }
+}
+
+fn attribute(e: &BytesStart, key: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|attr| attr.key == key)
+        .map(|attr| match attr.unescaped_value() {
+            Ok(value) => String::from_utf8_lossy(&value).into_owned(),
+            Err(_) => String::from_utf8_lossy(&attr.value).into_owned(),
+        })
+}
+
+/// A position or size, which ALTO allows to be fractional; ``None`` if missing or negative.
+fn number(e: &BytesStart, key: &[u8]) -> Option<u32> {
+    let n: f64 = attribute(e, key)?.trim().parse().ok()?;
+    if n >= 0.0 && n <= f64::from(u16::MAX) {
+        Some(n.round() as u32)
+    } else {
+        None
+    }
+}
+
+/// The resolution implied by a ``MeasurementUnit``; pixels say nothing about it.
+fn unit_dpi(unit: &str) -> Option<u32> {
+    match unit.trim() {
+        "inch1200" => Some(1200),
+        "mm10" => Some(254),
+        _ => None,
+    }
+}
+
+/// Coordinates of a ``String`` from its position and size, with its line's baseline.
+fn string_coords(e: &BytesStart, base: Option<u32>) -> Option<WordCoords> {
+    let left = number(e, b"HPOS")?;
+    let top = number(e, b"VPOS")?;
+    let right = left + number(e, b"WIDTH")?;
+    let bottom = top + number(e, b"HEIGHT")?;
+    let max = u32::from(u16::MAX);
+    if right > max || bottom > max {
+        return None;
+    }
+    Some(WordCoords {
+        x1: left as u16,
+        y1: top as u16,
+        x2: right as u16,
+        y2: bottom as u16,
+        base: base.unwrap_or(bottom).min(max) as u16,
+    })
+}
+
+/// Send the word held back by ``parse_alto``, if any.
+fn flush<F: FnMut(RichDjVu)>(pending: &mut Option<(Option<WordCoords>, String)>, callback: &mut F) {
+    if let Some((coords, text)) = pending.take() {
+        callback(RichDjVu::Word(coords, text));
+    }
+}
+
+/// Parse an ALTO document into the same events as ``parse_rich_xml``.
+///
+/// Pages are ``Page`` elements, lines ``TextLine`` (with their ``BASELINE`` as every word's
+/// baseline) and words ``String`` elements, whatever blocks they are grouped in. A hyphen
+/// (``HYP``) at the end of a line is kept on its word, as DjVu XML would have it. Positions
+/// in ``inch1200`` or ``mm10`` units are kept as is, with the matching DPI; pages measured in
+/// pixels have no DPI, since ALTO does not record one.
+pub fn parse_alto<R, F>(reader: R, mut callback: F)
+where
+    R: BufRead,
+    F: FnMut(RichDjVu),
+{
+    let mut xml = Reader::from_reader(reader);
+    xml.check_end_names(false);
+    xml.expand_empty_elements(true);
+    let mut buf = Vec::new();
+    let mut in_unit = false;
+    let mut dpi: Option<u32> = None;
+    let mut baseline: Option<u32> = None;
+    // The last word is held back in case a ``HYP`` follows it.
+    let mut pending: Option<(Option<WordCoords>, String)> = None;
+    loop {
+        match xml.read_event(&mut buf) {
+            Ok(Event::Eof) => break,
+            Ok(Event::Start(ref e)) => match local_name(e.name()) {
+                b"MeasurementUnit" => in_unit = true,
+                b"Page" => {
+                    flush(&mut pending, &mut callback);
+                    callback(RichDjVu::StartPage);
+                    if let (Some(w), Some(h)) = (number(e, b"WIDTH"), number(e, b"HEIGHT")) {
+                        callback(RichDjVu::PageDimensions(w, h));
+                    }
+                    if let Some(dpi) = dpi {
+                        callback(RichDjVu::PageDPI(dpi));
+                    }
+                }
+                b"TextLine" => {
+                    flush(&mut pending, &mut callback);
+                    callback(RichDjVu::StartLine);
+                    baseline = number(e, b"BASELINE");
+                }
+                b"String" => {
+                    flush(&mut pending, &mut callback);
+                    let text = attribute(e, b"CONTENT").unwrap_or_default();
+                    if !text.trim().is_empty() {
+                        pending = Some((string_coords(e, baseline), text.trim().to_owned()));
+                    }
+                }
+                b"HYP" => {
+                    if let Some((_, ref mut text)) = pending {
+                        text.push_str(&attribute(e, b"CONTENT").unwrap_or_else(|| "-".to_owned()));
+                    }
+                }
+                _ => {}
+            },
+            Ok(Event::Text(ref e)) if in_unit => {
+                if let Ok(unit) = e.unescape_and_decode(&xml) {
+                    dpi = unit_dpi(&unit);
+                }
+            }
+            Ok(Event::End(ref e)) => match local_name(e.name()) {
+                b"MeasurementUnit" => in_unit = false,
+                b"TextLine" => {
+                    flush(&mut pending, &mut callback);
+                    baseline = None;
+                    callback(RichDjVu::EndLine);
+                }
+                b"Page" => {
+                    flush(&mut pending, &mut callback);
+                    callback(RichDjVu::EndPage);
+                }
+                _ => {}
+            },
+            Err(e) => {
+                callback(RichDjVu::Error(format!("{:?}", e)));
+                break;
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+    flush(&mut pending, &mut callback);
+}
+
+/// Collect a whole ALTO document into a ``Book``, like ``process_book`` does for DjVu XML.
+pub fn process_alto<R: BufRead>(reader: R) -> Result<Book, String> {
+    ::book_from_events(
+        |callback| parse_alto(reader, callback),
+        &CoordsRepair::default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pagenum::label_pages;
+
+    const XML: &str = r#"<OBJECT height="3000" width="2000"><PARAM name="DPI" value="600"/>
+<LINE><WORD coords="814,1248,1012,1168,1240">Fish</WORD><WORD coords="1030,1250,1300,1170,1240">&amp;</WORD><WORD coords="1330,1250,1500,1170,1240">Chips</WORD></LINE>
+<LINE></LINE>
+</OBJECT>
+<OBJECT height="3000" width="2000"><PARAM name="DPI" value="600"/></OBJECT>"#;
+
+    #[test]
+    fn writes_pages_lines_and_words() {
+        let mut book = ::process_book(XML.as_bytes()).unwrap();
+        label_pages(&mut book);
+        let mut out = Vec::new();
+        write_alto(&book, &[None, Some(0.25)], &mut out).unwrap();
+        let alto = String::from_utf8(out).unwrap();
+        assert!(
+            alto.contains("<OtherTag ID=\"P2_POETRY\" TYPE=\"poetry-score\" LABEL=\"0.2500\"/>")
+        );
+        assert!(alto.contains(
+            "<Page ID=\"P2\" PHYSICAL_IMG_NR=\"2\" WIDTH=\"2000\" HEIGHT=\"3000\" TAGREFS=\"P2_POETRY\">"
+        ));
+        assert!(alto.contains(
+            "<TextLine ID=\"P1_L1\" HPOS=\"814\" VPOS=\"1168\" WIDTH=\"686\" HEIGHT=\"82\" BASELINE=\"1240\">"
+        ));
+        assert!(alto.contains(
+            "<SP/><String ID=\"P1_L1_S2\" CONTENT=\"&amp;\" HPOS=\"1030\" VPOS=\"1170\" WIDTH=\"270\" HEIGHT=\"80\"/>"
+        ));
+        assert_eq!(1, alto.matches("<TextBlock").count());
+        assert_eq!(1, alto.matches("<TextLine").count());
+    }
+
+    const ALTO: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<alto:alto xmlns:alto="http://www.loc.gov/standards/alto/ns-v3#">
+<alto:Description><alto:MeasurementUnit>mm10</alto:MeasurementUnit></alto:Description>
+<alto:Layout><alto:Page ID="P1" WIDTH="2100" HEIGHT="2970.4"><alto:PrintSpace>
+<alto:TextBlock ID="B1"><alto:TextLine BASELINE="140" HPOS="100" VPOS="100" WIDTH="900" HEIGHT="50">
+<alto:String CONTENT="Season" HPOS="100" VPOS="100" WIDTH="300" HEIGHT="50"/><alto:SP/>
+<alto:String CONTENT="of" HPOS="420" VPOS="100" WIDTH="60" HEIGHT="50"/><alto:SP/>
+<alto:String CONTENT="mel" HPOS="500" VPOS="100" WIDTH="100.6" HEIGHT="50"/><alto:HYP CONTENT="-"/>
+</alto:TextLine></alto:TextBlock>
+<alto:TextBlock><alto:TextLine><alto:String CONTENT="low &amp; ripe"/></alto:TextLine></alto:TextBlock>
+</alto:PrintSpace></alto:Page><alto:Page/></alto:Layout></alto:alto>"#;
+
+    #[test]
+    fn reads_pages_lines_and_words() {
+        let book = process_alto(ALTO.as_bytes()).unwrap();
+        assert_eq!(2, book.pages.len());
+        let page = &book.pages[0];
+        assert_eq!((2100, 2970, 254), (page.width, page.height, page.dpi));
+        assert_eq!("Season\tof\tmel-\n\n", page.text());
+        assert_eq!(
+            WordCoords {
+                x1: 500,
+                y1: 100,
+                x2: 601,
+                y2: 150,
+                base: 140,
+            },
+            page.lines[0][2].coords
+        );
+        // A line with no positions at all cannot be placed.
+        assert!(page.lines[1].is_empty());
+        assert_eq!(1, book.coords.dropped);
+        assert_eq!(0, book.pages[1].width);
+    }
+
+    #[test]
+    fn round_trip() {
+        let book = process_alto(ALTO.as_bytes()).unwrap();
+        let mut written = Vec::new();
+        write_alto(&book, &[], &mut written).unwrap();
+        let reread = process_alto(&written[..]).unwrap();
+        // Empty lines are not written.
+        assert_eq!(book.pages[0].lines[..1], reread.pages[0].lines[..]);
+        assert_eq!(book.pages.len(), reread.pages.len());
+    }
+}
@@ -0,0 +1,175 @@
+use format::{process_any_book, Format};
+use meta::BookMetadata;
+use std::io::{self, BufRead, Write};
+use types::{Book, FastDjVu};
+use FastEvents;
+
+/// Bumped whenever ``Book`` changes in a way old caches cannot be read as.
+pub const CACHE_VERSION: u32 = 1;
+
+/// One line of a book cache: a parsed book and the name it had in the archive.
+///
+/// A cache is JSON lines, one book per line, so it can be appended to, split and streamed
+/// like the archives it replaces. Books are cached as ``process_book`` left them, before any
+/// text normalization, so every option of the tools still applies.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedBook {
+    pub version: u32,
+    pub name: String,
+    pub book: Book,
+}
+
+/// Is this path a book cache rather than an archive of DJVU XML?
+pub fn is_cache(path: &str) -> bool {
+    path.ends_with(".jsonl")
+}
+
+/// Append a book to a cache.
+pub fn write_cached_book<W: Write>(out: &mut W, name: &str, book: &Book) -> io::Result<()> {
+    #[derive(Serialize)]
+    struct Borrowed<'a> {
+        version: u32,
+        name: &'a str,
+        book: &'a Book,
+    }
+    serde_json::to_writer(
+        &mut *out,
+        &Borrowed {
+            version: CACHE_VERSION,
+            name,
+            book,
+        },
+    )?;
+    writeln!(out)
+}
+
+/// The books of a cache, in order.
+pub struct CacheReader<R> {
+    lines: io::Lines<R>,
+}
+
+impl<R: BufRead> CacheReader<R> {
+    pub fn new(reader: R) -> CacheReader<R> {
+        CacheReader {
+            lines: reader.lines(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for CacheReader<R> {
+    type Item = io::Result<CachedBook>;
+
+    fn next(&mut self) -> Option<io::Result<CachedBook>> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            return Some(
+                serde_json::from_str(&line)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+                    .and_then(|cached: CachedBook| {
+                        if cached.version == CACHE_VERSION {
+                            Ok(cached)
+                        } else {
+                            Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                format!(
+                                    "{} was cached by version {} (expected {}); rebuild the cache.",
+                                    cached.name, cached.version, CACHE_VERSION
+                                ),
+                            ))
+                        }
+                    }),
+            );
+        }
+    }
+}
+
+/// A book as a tool receives it: still to be parsed (DjVu XML, hOCR, ALTO or plain text),
+/// perhaps with its item's metadata, or already parsed from a cache.
+pub enum BookSource<'a> {
+    Xml(&'a mut dyn BufRead),
+    Item(&'a mut dyn BufRead, BookMetadata),
+    Parsed(Book),
+}
+
+impl<'a> BookSource<'a> {
+    /// The parsed book, parsing the markup in whatever format it is if need be.
+    pub fn into_book(self) -> Result<Book, String> {
+        match self {
+            BookSource::Xml(reader) => process_any_book(reader),
+            BookSource::Item(reader, metadata) => {
+                let mut book = process_any_book(reader)?;
+                book.metadata = Some(metadata);
+                Ok(book)
+            }
+            BookSource::Parsed(book) => Ok(book),
+        }
+    }
+}
+
+impl<'a> FastEvents for BookSource<'a> {
+    fn fast_events<F: FnMut(FastDjVu)>(self, callback: F) {
+        match self {
+            BookSource::Xml(reader) | BookSource::Item(reader, _) => {
+                Format::detect(reader).parse_fast(reader, callback)
+            }
+            BookSource::Parsed(book) => (&book).fast_events(callback),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dump::dump_text;
+
+    const XML: &str = r#"<OBJECT height="3000" width="2000"><PARAM name="DPI" value="600"/>
+<LINE><WORD coords="814,1248,1012,1168,1247">O'er</WORD><WORD coords="1030,1248,1300,1168,1247">hills,</WORD></LINE>
+<LINE><WORD coords="814,1348,1012,1268,1347">Tʜe</WORD><WORD coords="1,2,3">&amp;c.</WORD></LINE>
+</OBJECT>
+<OBJECT height="3000" width="2000"><HIDDENTEXT/></OBJECT>"#;
+
+    #[test]
+    fn round_trip() {
+        let book = ::process_book(XML.as_bytes()).unwrap();
+        let mut cache = Vec::new();
+        write_cached_book(&mut cache, "a_djvu.xml", &book).unwrap();
+        write_cached_book(&mut cache, "b_djvu.xml", &Book::new()).unwrap();
+        assert_eq!(2, cache.iter().filter(|&&b| b == b'\n').count());
+
+        let books: Vec<CachedBook> = CacheReader::new(&cache[..])
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(2, books.len());
+        assert_eq!("a_djvu.xml", books[0].name);
+        assert_eq!(book, books[0].book);
+        assert_eq!(Book::new(), books[1].book);
+    }
+
+    #[test]
+    fn same_events_as_xml() {
+        let mut from_xml = Vec::new();
+        dump_text(XML.as_bytes(), &mut from_xml).unwrap();
+        let book = BookSource::Xml(&mut XML.as_bytes()).into_book().unwrap();
+        let mut from_cache = Vec::new();
+        dump_text(BookSource::Parsed(book), &mut from_cache).unwrap();
+        assert_eq!(
+            String::from_utf8(from_xml).unwrap(),
+            String::from_utf8(from_cache).unwrap()
+        );
+    }
+
+    #[test]
+    fn stale_cache() {
+        let line = r#"{"version":0,"name":"a","book":{"pages":[],"coords":{"repaired":0,"inferred":0,"dropped":0}}}"#;
+        let mut books = CacheReader::new(line.as_bytes());
+        assert!(books.next().unwrap().is_err());
+        assert!(is_cache("books.jsonl"));
+        assert!(!is_cache("books.zip"));
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::{HashSet, VecDeque};
+use std::io;
+use text::tokenize;
+use types::FastDjVu;
+use types::FastDjVu::*;
+use FastEvents;
+
+/// A word that matched, with the events around it: keyword in context.
+///
+/// Context holds words and the page and line boundaries, written as ``<P>``, ``<L>``,
+/// ``</L>`` and ``</P>``; it is shorter than asked for at the start and end of a book.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextWindow {
+    pub book: String,
+    pub page: u32,
+    /// Index of the keyword among the words of its page.
+    pub word: u32,
+    pub keyword: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+impl ContextWindow {
+    /// A concordance line: the words before right-aligned in ``width`` characters (keeping
+    /// the nearest ones), then the keyword, then the words after. Boundaries are left out.
+    pub fn line(&self, width: usize) -> String {
+        let words = |events: &[String]| {
+            events
+                .iter()
+                .filter(|e| !is_marker(e))
+                .map(|e| e.as_str())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let before = words(&self.before);
+        let skip = before.chars().count().saturating_sub(width);
+        let before: String = before.chars().skip(skip).collect();
+        format!(
+            "{:>width$}  {}  {}",
+            before,
+            self.keyword,
+            words(&self.after),
+            width = width
+        )
+    }
+}
+
+fn is_marker(event: &str) -> bool {
+    matches!(event, "<P>" | "<L>" | "</L>" | "</P>")
+}
+
+/// Words matching any of a set of keywords, compared token by token in lowercase.
+#[derive(Debug, Default, Clone)]
+pub struct Keywords {
+    words: HashSet<String>,
+}
+
+impl Keywords {
+    pub fn new<I, S>(words: I) -> Keywords
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Keywords {
+            words: words
+                .into_iter()
+                .flat_map(|w| tokenize(w.as_ref()))
+                .collect(),
+        }
+    }
+    /// Does any token of this OCR word match, e.g. "Love," for "love"?
+    pub fn matches(&self, word: &str) -> bool {
+        tokenize(word).iter().any(|t| self.words.contains(t))
+    }
+}
+
+struct PositionedEvent {
+    page: u32,
+    word: u32,
+    event: FastDjVu,
+}
+
+fn as_str(x: &FastDjVu) -> &str {
+    match *x {
+        Word(ref w) => w.trim(),
+        StartPage => "<P>",
+        StartLine => "<L>",
+        EndLine => "</L>",
+        EndPage => "</P>",
+        Error(_) => "",
+    }
+}
+
+/// Events of a book in a sliding window; each is considered once it is at the center.
+struct Windows<'a, P, F> {
+    book: &'a str,
+    size: usize,
+    matches: P,
+    emit: F,
+    buffer: VecDeque<PositionedEvent>,
+    /// Index in ``buffer`` of the next event to consider.
+    center: usize,
+}
+
+impl<'a, P, F> Windows<'a, P, F>
+where
+    P: FnMut(&str) -> bool,
+    F: FnMut(ContextWindow) -> io::Result<()>,
+{
+    fn push(&mut self, event: PositionedEvent) -> io::Result<()> {
+        self.buffer.push_back(event);
+        while self.buffer.len() - self.center > self.size {
+            self.consider()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        while self.center < self.buffer.len() {
+            self.consider()?;
+        }
+        Ok(())
+    }
+
+    fn consider(&mut self) -> io::Result<()> {
+        let size = self.size;
+        let found = {
+            let center = &self.buffer[self.center];
+            match center.event {
+                Word(ref w) if (self.matches)(w) => {
+                    let context = |events: &mut dyn Iterator<Item = &PositionedEvent>| {
+                        events.map(|e| as_str(&e.event).to_owned()).collect()
+                    };
+                    Some(ContextWindow {
+                        book: self.book.to_owned(),
+                        page: center.page,
+                        word: center.word,
+                        keyword: w.trim().to_owned(),
+                        before: context(
+                            &mut self
+                                .buffer
+                                .iter()
+                                .take(self.center)
+                                .skip(self.center.saturating_sub(size)),
+                        ),
+                        after: context(&mut self.buffer.iter().skip(self.center + 1).take(size)),
+                    })
+                }
+                _ => None,
+            }
+        };
+        if let Some(window) = found {
+            (self.emit)(window)?;
+        }
+        self.center += 1;
+        if self.center > size {
+            self.buffer.pop_front();
+            self.center -= 1;
+        }
+        Ok(())
+    }
+}
+
+/// Stream every word of a book (DJVU XML or a parsed ``Book``) for which ``matches`` is true
+/// to ``emit``, with ``size`` events of context on either side.
+///
+/// Only ``size * 2 + 1`` events are held at once, so this works on books of any length.
+/// The first error from ``emit`` stops the search and is returned.
+pub fn context_windows<S, P, F>(
+    book: &str,
+    source: S,
+    size: usize,
+    matches: P,
+    emit: F,
+) -> io::Result<()>
+where
+    S: FastEvents,
+    P: FnMut(&str) -> bool,
+    F: FnMut(ContextWindow) -> io::Result<()>,
+{
+    let mut windows = Windows {
+        book,
+        size,
+        matches,
+        emit,
+        buffer: VecDeque::with_capacity(size * 2 + 1),
+        center: 0,
+    };
+    let mut page = 0;
+    let mut word_index = 0;
+    let mut error = None;
+    source.fast_events(|item| {
+        let event = match item {
+            Word(_) => {
+                word_index += 1;
+                PositionedEvent {
+                    page,
+                    word: word_index - 1,
+                    event: item,
+                }
+            }
+            Error(ref msg) => {
+                eprintln!("{:?}", msg);
+                return;
+            }
+            _ => {
+                let end_page = item == EndPage;
+                let event = PositionedEvent {
+                    page,
+                    word: word_index,
+                    event: item,
+                };
+                if end_page {
+                    page += 1;
+                    word_index = 0;
+                }
+                event
+            }
+        };
+        if error.is_none() {
+            if let Err(e) = windows.push(event) {
+                error = Some(e);
+            }
+        }
+    });
+    match error {
+        Some(e) => Err(e),
+        None => windows.finish(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<OBJECT height="3000" width="2000"><PARAM name="DPI" value="600"/>
+<LINE><WORD coords="1,2,3,4,5">Love</WORD><WORD coords="1,2,3,4,5">is</WORD><WORD coords="1,2,3,4,5">not</WORD></LINE>
+</OBJECT>
+<OBJECT height="3000" width="2000"><PARAM name="DPI" value="600"/>
+<LINE><WORD coords="1,2,3,4,5">love,</WORD><WORD coords="1,2,3,4,5">which</WORD></LINE>
+</OBJECT>"#;
+
+    fn windows(size: usize, keywords: &Keywords) -> Vec<ContextWindow> {
+        let mut found = Vec::new();
+        context_windows(
+            "book",
+            XML.as_bytes(),
+            size,
+            |w| keywords.matches(w),
+            |window| {
+                found.push(window);
+                Ok(())
+            },
+        )
+        .unwrap();
+        found
+    }
+
+    #[test]
+    fn keywords_across_pages() {
+        let found = windows(4, &Keywords::new(["LOVE"]));
+        assert_eq!(2, found.len());
+        assert_eq!((0, 0), (found[0].page, found[0].word));
+        assert_eq!(vec!["<P>", "<L>"], found[0].before);
+        assert_eq!(vec!["is", "not", "</L>", "</P>"], found[0].after);
+        assert_eq!((1, 0), (found[1].page, found[1].word));
+        assert_eq!("love,", found[1].keyword);
+        assert_eq!(vec!["</L>", "</P>", "<P>", "<L>"], found[1].before);
+        assert_eq!(vec!["which", "</L>", "</P>"], found[1].after);
+    }
+
+    #[test]
+    fn nothing_matches() {
+        assert!(windows(2, &Keywords::new(["hate"])).is_empty());
+        assert!(windows(0, &Keywords::default()).is_empty());
+    }
+
+    #[test]
+    fn stops_at_first_error() {
+        let mut calls = 0;
+        let result = context_windows(
+            "book",
+            XML.as_bytes(),
+            1,
+            |_| true,
+            |_| {
+                calls += 1;
+                Err(io::Error::other("full"))
+            },
+        );
+        assert!(result.is_err());
+        assert_eq!(1, calls);
+    }
+
+    #[test]
+    fn concordance_line() {
+        let found = windows(8, &Keywords::new(["love"]));
+        assert_eq!("   Love is not  love,  which", found[1].line(14));
+        assert_eq!("is not  love,  which", found[1].line(6));
+    }
+}
@@ -1,17 +1,23 @@
 use std;
-use std::cmp::max;
+use std::cmp::{max, min};
 use types::WordCoords;
 
 #[derive(Clone, Debug)]
 enum CoordsParsingErr {
     Unicode(std::str::Utf8Error),
     Number(std::num::ParseIntError),
-    NumberOfNumbers(),
+    NumberOfNumbers(usize),
 }
 
 impl std::fmt::Display for CoordsParsingErr {
-    fn fmt(&self, _output: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-        Ok(())
+    fn fmt(&self, output: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match *self {
+            CoordsParsingErr::Unicode(ref e) => write!(output, "coords not utf-8: {}", e),
+            CoordsParsingErr::Number(ref e) => write!(output, "coords not numeric: {}", e),
+            CoordsParsingErr::NumberOfNumbers(n) => {
+                write!(output, "expected 4 or 5 coords, got {}", n)
+            }
+        }
     }
 }
 impl std::error::Error for CoordsParsingErr {
@@ -32,6 +38,66 @@ impl From<std::num::ParseIntError> for CoordsParsingErr {
     }
 }
 
+/// How hard to try when a ``coords`` attribute has been damaged by OCR.
+///
+/// The OCR engine writes the coordinates into the XML as text, and occasionally it
+/// mis-recognizes its own digits. Each kind of repair can be switched off separately:
+///
+/// - ``ocr_confusions``: a field that is not a number has look-alike characters mapped
+///   to digits: ``! l I i | ]`` become ``1``, ``O o D Q`` become ``0``, ``S s`` become ``5``,
+///   ``B`` becomes ``8`` and ``Z z`` become ``2``.
+/// - ``skip_empty_fields``: doubled, leading or trailing commas are ignored.
+/// - ``truncate_extra``: six numbers are accepted by keeping the first five.
+/// - ``infer_from_line``: a word whose coordinates are still unusable is placed between
+///   its neighbors on the same line (see ``infer_line``), instead of being dropped.
+///
+/// Three numbers (or fewer) are never guessed at directly; with ``infer_from_line`` the
+/// word is still placed from its neighbors.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoordsRepair {
+    pub ocr_confusions: bool,
+    pub skip_empty_fields: bool,
+    pub truncate_extra: bool,
+    pub infer_from_line: bool,
+}
+impl CoordsRepair {
+    /// Apply every repair we know about.
+    pub fn all() -> CoordsRepair {
+        CoordsRepair {
+            ocr_confusions: true,
+            skip_empty_fields: true,
+            truncate_extra: true,
+            infer_from_line: true,
+        }
+    }
+    /// Only accept coordinates that are exactly as the format describes them.
+    pub fn none() -> CoordsRepair {
+        CoordsRepair {
+            ocr_confusions: false,
+            skip_empty_fields: false,
+            truncate_extra: false,
+            infer_from_line: false,
+        }
+    }
+}
+impl Default for CoordsRepair {
+    fn default() -> Self {
+        CoordsRepair::all()
+    }
+}
+
+/// Map a character that OCR commonly confuses with a digit back to that digit.
+fn ocr_digit(c: char) -> char {
+    match c {
+        '!' | 'l' | 'I' | 'i' | '|' | ']' => '1',
+        'O' | 'o' | 'D' | 'Q' => '0',
+        'S' | 's' => '5',
+        'B' => '8',
+        'Z' | 'z' => '2',
+        other => other,
+    }
+}
+
 impl WordCoords {
     /// Consider the example coordinates:
     ///
@@ -41,49 +107,141 @@ impl WordCoords {
     /// <WORD coords="1068,1250,1548,1168,1248">TRAGEDY</WORD>
     ///
     /// We can infer that they are in the order: (x1,y2,x2,y1,base).
-    fn parse(bytes: &[u8]) -> Result<WordCoords, CoordsParsingErr> {
-        let coords: Result<Vec<u16>, CoordsParsingErr> = bytes
-            .split(|x| *x == b',')
-            .map(|coord_bytes| -> Result<u16, CoordsParsingErr> {
-                let coord_str = std::str::from_utf8(coord_bytes)?;
-                if coord_str.contains('!') {
-                    let coord_str = coord_str.to_string();
-                    Ok(coord_str.replace('!', "1").parse::<u16>()?)
-                } else {
-                    Ok(coord_str.parse::<u16>()?)
+    ///
+    /// Returns the coordinates and whether any repair was needed to get them.
+    fn parse(bytes: &[u8], repair: &CoordsRepair) -> Result<(WordCoords, bool), CoordsParsingErr> {
+        let text = std::str::from_utf8(bytes)?;
+        let mut repaired = false;
+        let mut coords: Vec<u16> = Vec::new();
+        for field in text.split(',') {
+            let field = field.trim();
+            if field.is_empty() && repair.skip_empty_fields {
+                repaired = true;
+                continue;
+            }
+            match field.parse::<u16>() {
+                Ok(n) => coords.push(n),
+                Err(e) => {
+                    if !repair.ocr_confusions {
+                        return Err(e.into());
+                    }
+                    let fixed: String = field.chars().map(ocr_digit).collect();
+                    coords.push(fixed.parse::<u16>()?);
+                    repaired = true;
                 }
-            })
-            .collect();
+            }
+        }
 
-        let coords = coords?;
+        if coords.len() == 6 && repair.truncate_extra {
+            coords.truncate(5);
+            repaired = true;
+        }
 
-        match coords.len() {
-            5 => Ok(WordCoords {
+        let found = match coords.len() {
+            5 => WordCoords {
                 x1: coords[0],
                 y2: coords[1],
                 x2: coords[2],
                 y1: coords[3],
                 base: coords[4],
-            }),
-            4 => Ok(WordCoords {
+            },
+            4 => WordCoords {
                 x1: coords[0],
                 y2: coords[1],
                 x2: coords[2],
                 y1: coords[3],
                 base: max(coords[1], coords[3]),
-            }),
-            _ => Err(CoordsParsingErr::NumberOfNumbers()),
-        }
+            },
+            n => return Err(CoordsParsingErr::NumberOfNumbers(n)),
+        };
+        Ok((found, repaired))
+    }
+    /// Parse a ``coords`` attribute, returning the coordinates and whether they were repaired.
+    pub(crate) fn parse_repair(bytes: &[u8], repair: &CoordsRepair) -> Option<(WordCoords, bool)> {
+        WordCoords::parse(bytes, repair).ok()
     }
+    #[cfg(test)]
     pub(crate) fn parse_opt(bytes: &[u8]) -> Option<WordCoords> {
-        match WordCoords::parse(bytes) {
-            Ok(x) => Some(x),
-            Err(_) => {
-                eprintln!("Couldn't parse: {:?}", std::str::from_utf8(bytes));
-                None
-            }
+        WordCoords::parse_repair(bytes, &CoordsRepair::default()).map(|(c, _)| c)
+    }
+}
+
+/// Average width of a character on this line, from the words whose coordinates we know.
+fn char_width(words: &[(Option<WordCoords>, String)]) -> u32 {
+    let (width, chars) = words
+        .iter()
+        .filter_map(|(c, text)| c.map(|c| (c, text.chars().count())))
+        .fold((0u32, 0u32), |(w, n), (c, len)| {
+            (w + u32::from(c.x2.saturating_sub(c.x1)), n + len as u32)
+        });
+    max(1, width / max(1, chars))
+}
+
+fn clamp(x: u32) -> u16 {
+    min(x, u32::from(u16::MAX)) as u16
+}
+
+/// Fill in missing coordinates on a line from the words around them.
+///
+/// Vertical extents come from the nearest known words on either side; horizontally, a run
+/// of unknown words is spread over the gap between its neighbors in proportion to their
+/// lengths, or laid out at the line's average character width if it is at the start or end
+/// of the line. A line with no known coordinates at all cannot be repaired.
+pub(crate) fn infer_line(words: &[(Option<WordCoords>, String)]) -> Vec<Option<WordCoords>> {
+    let mut output: Vec<Option<WordCoords>> = words.iter().map(|w| w.0).collect();
+    if output.iter().all(|c| c.is_none()) {
+        return output;
+    }
+    let cw = char_width(words);
+
+    let mut start = 0;
+    while start < words.len() {
+        if output[start].is_some() {
+            start += 1;
+            continue;
+        }
+        let mut end = start;
+        while end < words.len() && output[end].is_none() {
+            end += 1;
+        }
+        let prev = if start > 0 { output[start - 1] } else { None };
+        let next = if end < words.len() { output[end] } else { None };
+
+        let lengths: Vec<u32> = words[start..end]
+            .iter()
+            .map(|w| max(1, w.1.chars().count() as u32))
+            .collect();
+        let total: u32 = lengths.iter().sum::<u32>() + (lengths.len() as u32 + 1);
+        let (left, right) = match (prev, next) {
+            (Some(p), Some(n)) => (u32::from(p.x2), max(u32::from(p.x2), u32::from(n.x1))),
+            (Some(p), None) => (u32::from(p.x2), u32::from(p.x2) + total * cw),
+            (None, Some(n)) => (u32::from(n.x1).saturating_sub(total * cw), u32::from(n.x1)),
+            (None, None) => unreachable!(),
+        };
+        let unit = (right - left) as f64 / f64::from(total);
+
+        let (y1, y2, base) = match (prev, next) {
+            (Some(p), Some(n)) => (min(p.y1, n.y1), max(p.y2, n.y2), p.base),
+            (Some(c), None) | (None, Some(c)) => (c.y1, c.y2, c.base),
+            (None, None) => unreachable!(),
+        };
+
+        let mut offset = 1u32;
+        for (i, len) in lengths.iter().enumerate() {
+            let x1 = left + (f64::from(offset) * unit) as u32;
+            let x2 = left + (f64::from(offset + len) * unit) as u32;
+            output[start + i] = Some(WordCoords {
+                x1: clamp(x1),
+                y1,
+                x2: clamp(x2),
+                y2,
+                base,
+            });
+            offset += len + 1;
         }
+        start = end;
     }
+    output
 }
 
 #[cfg(test)]
@@ -125,4 +283,64 @@ mod tests {
         };
         assert_eq!(t2, e2);
     }
+
+    #[test]
+    fn ocr_confusions() {
+        let expected = WordCoords::parse_opt(b"814,1248,1012,1168,1247").unwrap();
+        let (found, repaired) =
+            WordCoords::parse_repair(b"8l4,I248,1O12,1I68,l247", &CoordsRepair::all()).unwrap();
+        assert!(repaired);
+        assert_eq!(expected, found);
+        assert_eq!(
+            None,
+            WordCoords::parse_repair(b"8l4,1248,1012,1168,1247", &CoordsRepair::none())
+        );
+    }
+
+    #[test]
+    fn extra_commas_and_numbers() {
+        let expected = WordCoords::parse_opt(b"814,1248,1012,1168,1247").unwrap();
+        assert_eq!(
+            Some(expected),
+            WordCoords::parse_opt(b"814,,1248,1012,1168,1247,")
+        );
+        assert_eq!(
+            Some(expected),
+            WordCoords::parse_opt(b"814,1248,1012,1168,1247,9")
+        );
+        assert_eq!(None, WordCoords::parse_opt(b"814,1248,1012"));
+        assert_eq!(
+            None,
+            WordCoords::parse_repair(b"814,1248,1012,1168,1247,9", &CoordsRepair::none())
+        );
+    }
+
+    #[test]
+    fn infer_between_neighbors() {
+        let the = WordCoords::parse_opt(b"814,1248,1012,1168,1247");
+        let tragedy = WordCoords::parse_opt(b"1068,1250,1548,1168,1248");
+        let line = vec![
+            (the, "THE".to_owned()),
+            (None, "OF".to_owned()),
+            (tragedy, "TRAGEDY".to_owned()),
+        ];
+        let found = infer_line(&line);
+        assert_eq!(the, found[0]);
+        assert_eq!(tragedy, found[2]);
+        let of = found[1].unwrap();
+        assert!(of.x1 > 1012 && of.x2 < 1068 && of.x1 < of.x2);
+        assert_eq!((1168, 1250, 1247), (of.y1, of.y2, of.base));
+    }
+
+    #[test]
+    fn infer_at_line_end() {
+        let the = WordCoords::parse_opt(b"814,1248,1012,1168,1247");
+        let line = vec![(the, "THE".to_owned()), (None, "END".to_owned())];
+        let end = infer_line(&line)[1].unwrap();
+        assert!(end.x1 > 1012);
+        assert_eq!(the.unwrap().y1, end.y1);
+
+        let hopeless = vec![(None, "THE".to_owned()), (None, "END".to_owned())];
+        assert_eq!(vec![None, None], infer_line(&hopeless));
+    }
 }
@@ -0,0 +1,119 @@
+use cache::BookSource;
+use export::{page_text, TextOptions};
+use format::Format;
+use std::io::{self, BufRead, Write};
+use text::tokenize;
+use types::FastDjVu::*;
+use FastEvents;
+
+/// Keep the first error from a callback that cannot return one.
+fn first_error(slot: &mut Option<io::Error>, result: io::Result<()>) {
+    if let Err(e) = result {
+        if slot.is_none() {
+            *slot = Some(e);
+        }
+    }
+}
+
+/// Write every rich event of a DjVu XML, hOCR or ALTO document, one per line, in ``Debug`` form.
+pub fn dump_rich<R: BufRead, W: Write>(mut reader: R, out: &mut W) -> io::Result<()> {
+    let mut error = None;
+    Format::detect(&mut reader).parse_rich(reader, |item| {
+        first_error(&mut error, writeln!(out, "{:?}", item))
+    });
+    error.map_or(Ok(()), Err)
+}
+
+/// Write the words of a book as they come, one line of text per line of the page.
+pub fn dump_text<S: FastEvents, W: Write>(source: S, out: &mut W) -> io::Result<()> {
+    let mut error = None;
+    source.fast_events(|item| {
+        let result = match item {
+            StartPage | StartLine => Ok(()),
+            Word(word) => write!(out, "{} ", word),
+            EndLine | EndPage => writeln!(out),
+            Error(msg) => {
+                eprintln!("{:?}", msg);
+                Ok(())
+            }
+        };
+        first_error(&mut error, result)
+    });
+    error.map_or(Ok(()), Err)
+}
+
+/// ``dump_text``, but a page at a time in reading order and exported according to
+/// ``options``, with a blank line after each page. With no model to tell, every page is
+/// treated as prose, so ``options.dehyphenate`` applies throughout.
+pub fn dump_text_with<W: Write>(
+    source: BookSource,
+    options: &TextOptions,
+    out: &mut W,
+) -> io::Result<()> {
+    let book = source
+        .into_book()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    for page in &book.pages {
+        writeln!(out, "{}", page_text(page, options, false))?;
+    }
+    Ok(())
+}
+
+/// Write the tokens of a book one per line, with a blank line after each line of the page.
+pub fn dump_words<S: FastEvents, W: Write>(source: S, out: &mut W) -> io::Result<()> {
+    let mut error = None;
+    source.fast_events(|item| {
+        let result = match item {
+            StartPage | StartLine => Ok(()),
+            Word(word) => tokenize(&word)
+                .iter()
+                .try_for_each(|w| writeln!(out, "{}", w)),
+            EndLine | EndPage => writeln!(out),
+            Error(msg) => {
+                eprintln!("{:?}", msg);
+                Ok(())
+            }
+        };
+        first_error(&mut error, result)
+    });
+    error.map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use export::Separator;
+
+    const PAGE: &str = r#"<OBJECT height="3000" width="2000"><PARAM name="DPI" value="600"/>
+<LINE><WORD coords="814,1248,1012,1168,1247">O'er</WORD><WORD coords="1030,1248,1300,1168,1247">hills,</WORD></LINE>
+</OBJECT>"#;
+
+    #[test]
+    fn dumps() {
+        let mut text = Vec::new();
+        dump_text(PAGE.as_bytes(), &mut text).unwrap();
+        assert_eq!("O'er hills, \n\n", String::from_utf8(text).unwrap());
+
+        let options = TextOptions {
+            separator: Separator::Space,
+            ..TextOptions::default()
+        };
+        let mut exported = Vec::new();
+        dump_text_with(
+            BookSource::Xml(&mut PAGE.as_bytes()),
+            &options,
+            &mut exported,
+        )
+        .unwrap();
+        assert_eq!("O'er hills,\n\n", String::from_utf8(exported).unwrap());
+
+        let mut words = Vec::new();
+        dump_words(PAGE.as_bytes(), &mut words).unwrap();
+        assert_eq!("o'er\nhills\n\n\n", String::from_utf8(words).unwrap());
+
+        let mut rich = Vec::new();
+        dump_rich(PAGE.as_bytes(), &mut rich).unwrap();
+        let rich = String::from_utf8(rich).unwrap();
+        assert!(rich.starts_with("StartPage\nPageDimensions(2000, 3000)\n"));
+    }
+}
@@ -0,0 +1,181 @@
+use types::BookPage;
+
+/// What to put between the words of a line.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Separator {
+    Tab,
+    Space,
+}
+
+impl Separator {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            Separator::Tab => "\t",
+            Separator::Space => " ",
+        }
+    }
+}
+
+/// How to turn a page into plain text.
+///
+/// The default keeps the text exactly as the OCR gave it: tab-separated words and one line
+/// of text per line of the page.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextOptions {
+    pub separator: Separator,
+    /// Rejoin words hyphenated across a line break ("beau-" / "tiful"), on prose pages only.
+    pub dehyphenate: bool,
+    /// Trim words, collapse whitespace inside them, and skip words that are only whitespace.
+    pub normalize_whitespace: bool,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions {
+            separator: Separator::Tab,
+            dehyphenate: false,
+            normalize_whitespace: false,
+        }
+    }
+}
+
+fn normalize(word: &str) -> String {
+    word.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Does this word end in a hyphen that was probably added to break it across lines?
+fn broken_word(word: &str) -> bool {
+    let mut chars = word.chars().rev();
+    chars.next() == Some('-') && chars.next().map(|c| c.is_alphabetic()).unwrap_or(false)
+}
+
+fn starts_lowercase(word: &str) -> bool {
+    word.chars()
+        .next()
+        .map(|c| c.is_lowercase())
+        .unwrap_or(false)
+}
+
+/// Join hyphenated line breaks: "beau-" at the end of one line and "tiful" at the start of the
+/// next become "beautiful" on the first line. Only a lowercase continuation is joined, so
+/// "Anglo-" / "Saxon" stays as it was.
+fn dehyphenate(lines: &mut Vec<Vec<String>>) {
+    for i in 0..lines.len().saturating_sub(1) {
+        let joins = match (lines[i].last(), lines[i + 1].first()) {
+            (Some(last), Some(first)) => broken_word(last) && starts_lowercase(first),
+            _ => false,
+        };
+        if joins {
+            let rest = lines[i + 1].remove(0);
+            let last = lines[i].last_mut().unwrap();
+            last.pop();
+            last.push_str(&rest);
+        }
+    }
+    lines.retain(|l| !l.is_empty());
+}
+
+/// The text of a page in reading order, according to ``options``.
+///
+/// Poetry keeps its line breaks, so ``options.dehyphenate`` only applies when ``is_poetry``
+/// is false.
+pub fn page_text(page: &BookPage, options: &TextOptions, is_poetry: bool) -> String {
+    let ordered = page.in_reading_order();
+    let mut lines: Vec<Vec<String>> = ordered
+        .lines
+        .iter()
+        .map(|l| {
+            l.iter()
+                .map(|w| {
+                    if options.normalize_whitespace {
+                        normalize(&w.text)
+                    } else {
+                        w.text.clone()
+                    }
+                })
+                .filter(|w| !options.normalize_whitespace || !w.is_empty())
+                .collect()
+        })
+        .collect();
+    if options.dehyphenate && !is_poetry {
+        dehyphenate(&mut lines);
+    }
+
+    let mut text = String::new();
+    for line in lines {
+        text.push_str(&line.join(options.separator.as_str()));
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::{BookWord, WordCoords};
+
+    fn page(lines: &[&[&str]]) -> BookPage {
+        let mut page = BookPage::new();
+        for (row, words) in lines.iter().enumerate() {
+            let top = 100 * row as u16;
+            page.lines.push(
+                words
+                    .iter()
+                    .enumerate()
+                    .map(|(col, t)| {
+                        let x1 = 200 * col as u16;
+                        BookWord::new(
+                            WordCoords {
+                                x1,
+                                y1: top,
+                                x2: x1 + 150,
+                                y2: top + 50,
+                                base: top + 50,
+                            },
+                            t.to_string(),
+                        )
+                    })
+                    .collect(),
+            );
+        }
+        page
+    }
+
+    #[test]
+    fn default_matches_raw_text() {
+        let p = page(&[&["a", "beau-"], &["tiful", "day"]]);
+        assert_eq!(
+            "a\tbeau-\ntiful\tday\n",
+            page_text(&p, &TextOptions::default(), false)
+        );
+        assert_eq!(p.text(), page_text(&p, &TextOptions::default(), false));
+    }
+
+    #[test]
+    fn dehyphenates_prose_only() {
+        let p = page(&[&["a", "beau-"], &["tiful"], &["Anglo-"], &["Saxon", "day"]]);
+        let options = TextOptions {
+            separator: Separator::Space,
+            dehyphenate: true,
+            normalize_whitespace: false,
+        };
+        assert_eq!(
+            "a beautiful\nAnglo-\nSaxon day\n",
+            page_text(&p, &options, false)
+        );
+        assert_eq!(
+            "a beau-\ntiful\nAnglo-\nSaxon day\n",
+            page_text(&p, &options, true)
+        );
+    }
+
+    #[test]
+    fn normalizes_whitespace() {
+        let p = page(&[&[" the ", "  ", "end\u{a0}"]]);
+        let options = TextOptions {
+            normalize_whitespace: true,
+            ..TextOptions::default()
+        };
+        assert_eq!("the\tend\n", page_text(&p, &options, false));
+    }
+}
@@ -0,0 +1,131 @@
+use alto::{parse_alto, process_alto};
+use hocr::{parse_hocr, process_hocr};
+use plaintext::{parse_text, process_text, PlainTextOptions};
+use std::io::BufRead;
+use types::{Book, FastDjVu, RichDjVu};
+
+/// The formats we can read books from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    /// Internet Archive ``_djvu.xml``.
+    DjVu,
+    /// hOCR, as from HathiTrust or Tesseract.
+    Hocr,
+    /// ALTO, as from newspaper digitization projects.
+    Alto,
+    /// Plain text, as from Project Gutenberg, laid out with made-up coordinates.
+    Text,
+}
+
+impl Format {
+    /// Guess the format of a document from its first buffered bytes, without consuming them.
+    ///
+    /// Anything not starting with a tag is plain text; markup we cannot recognize is assumed to
+    /// be DjVu XML, so fragments still parse.
+    pub fn detect<R: BufRead + ?Sized>(reader: &mut R) -> Format {
+        let start = match reader.fill_buf() {
+            Ok(bytes) => String::from_utf8_lossy(bytes).to_lowercase(),
+            Err(_) => return Format::DjVu,
+        };
+        let first = start
+            .trim_start_matches(|c: char| c.is_whitespace() || c == '\u{feff}')
+            .chars()
+            .next();
+        if first.is_some_and(|c| c != '<') {
+            Format::Text
+        } else if start.contains("<djvuxml") || start.contains("doctype djvuxml") {
+            Format::DjVu
+        } else if start.contains("<alto") || start.contains(":alto") {
+            Format::Alto
+        } else if start.contains("ocr_page") || start.contains("<html") {
+            Format::Hocr
+        } else {
+            Format::DjVu
+        }
+    }
+
+    /// Parse a document of this format into ``RichDjVu`` events.
+    pub fn parse_rich<R: BufRead, F: FnMut(RichDjVu)>(self, reader: R, callback: F) {
+        match self {
+            Format::DjVu => ::parse_rich_xml(reader, callback),
+            Format::Hocr => parse_hocr(reader, callback),
+            Format::Alto => parse_alto(reader, callback),
+            Format::Text => parse_text(reader, &PlainTextOptions::default(), callback),
+        }
+    }
+
+    /// Parse a document of this format into ``FastDjVu`` events.
+    pub fn parse_fast<R: BufRead, F: FnMut(FastDjVu)>(self, reader: R, mut callback: F) {
+        if self == Format::DjVu {
+            return ::parse_fast_xml(reader, callback);
+        }
+        self.parse_rich(reader, |item| match item {
+            RichDjVu::StartPage => callback(FastDjVu::StartPage),
+            RichDjVu::StartLine => callback(FastDjVu::StartLine),
+            RichDjVu::Word(_, text) => callback(FastDjVu::Word(text)),
+            RichDjVu::EndLine => callback(FastDjVu::EndLine),
+            RichDjVu::EndPage => callback(FastDjVu::EndPage),
+            RichDjVu::Error(msg) => callback(FastDjVu::Error(msg)),
+            _ => {}
+        })
+    }
+
+    /// Collect a whole document of this format into a ``Book``.
+    pub fn process_book<R: BufRead>(self, reader: R) -> Result<Book, String> {
+        match self {
+            Format::DjVu => ::process_book(reader),
+            Format::Hocr => process_hocr(reader),
+            Format::Alto => process_alto(reader),
+            Format::Text => process_text(reader, &PlainTextOptions::default()),
+        }
+    }
+}
+
+/// Collect a whole DjVu XML, hOCR, ALTO or plain text document into a ``Book``.
+pub fn process_any_book<R: BufRead>(mut reader: R) -> Result<Book, String> {
+    Format::detect(&mut reader).process_book(reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_formats() {
+        let detect = |text: &str| Format::detect(&mut text.as_bytes());
+        assert_eq!(
+            Format::DjVu,
+            detect("<?xml version=\"1.0\"?>\n<!DOCTYPE DjVuXML PUBLIC>")
+        );
+        assert_eq!(Format::DjVu, detect("<OBJECT height=\"3000\">"));
+        assert_eq!(Format::Alto, detect("<?xml?><alto xmlns=\"\">"));
+        assert_eq!(Format::Hocr, detect("<!DOCTYPE html><html>"));
+        assert_eq!(
+            Format::Hocr,
+            detect("<html><meta name=\"ocr-system\" content=\"djvuxml\" />")
+        );
+        assert_eq!(Format::DjVu, detect(""));
+        assert_eq!(Format::Text, detect("\u{feff}The Project Gutenberg EBook"));
+    }
+
+    #[test]
+    fn same_book_from_every_format() {
+        let hocr = r#"<html><body><div class="ocr_page" title="bbox 0 0 2000 3000; scan_res 600 600">
+<span class="ocr_line" title="bbox 100 100 400 150; baseline 0 -5"><span class="ocrx_word" title="bbox 100 100 400 150">Hark!</span></span>
+</div></body></html>"#;
+        let book = process_any_book(hocr.as_bytes()).unwrap();
+        assert_eq!("Hark!\n", book.pages[0].text());
+        let mut words = Vec::new();
+        Format::Hocr.parse_fast(hocr.as_bytes(), |e| words.push(e));
+        assert_eq!(
+            vec![
+                FastDjVu::StartPage,
+                FastDjVu::StartLine,
+                FastDjVu::Word("Hark!".to_owned()),
+                FastDjVu::EndLine,
+                FastDjVu::EndPage,
+            ],
+            words
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use std::cmp::{max, min};
+use types::{BookPage, BookWord, WordCoords};
+
+/// Points per inch, as used by typesetters and PDF.
+const POINTS_PER_INCH: f64 = 72.0;
+
+/// A bounding box in page pixels, with ``left <= right`` and ``top <= bottom``.
+///
+/// ``WordCoords`` keep whatever order the XML used; use ``WordCoords::bbox`` to get one of these.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BBox {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+impl BBox {
+    pub fn new(x1: u32, y1: u32, x2: u32, y2: u32) -> BBox {
+        BBox {
+            left: min(x1, x2),
+            top: min(y1, y2),
+            right: max(x1, x2),
+            bottom: max(y1, y2),
+        }
+    }
+    pub fn width(&self) -> u32 {
+        self.right - self.left
+    }
+    pub fn height(&self) -> u32 {
+        self.bottom - self.top
+    }
+    pub fn area(&self) -> u64 {
+        u64::from(self.width()) * u64::from(self.height())
+    }
+    pub fn center_x(&self) -> f64 {
+        f64::from(self.left + self.right) / 2.0
+    }
+    pub fn center_y(&self) -> f64 {
+        f64::from(self.top + self.bottom) / 2.0
+    }
+    /// The smallest box containing both boxes.
+    pub fn union(&self, other: &BBox) -> BBox {
+        BBox {
+            left: min(self.left, other.left),
+            top: min(self.top, other.top),
+            right: max(self.right, other.right),
+            bottom: max(self.bottom, other.bottom),
+        }
+    }
+    /// The overlap of both boxes, if they overlap at all.
+    pub fn intersection(&self, other: &BBox) -> Option<BBox> {
+        let left = max(self.left, other.left);
+        let top = max(self.top, other.top);
+        let right = min(self.right, other.right);
+        let bottom = min(self.bottom, other.bottom);
+        if left < right && top < bottom {
+            Some(BBox {
+                left,
+                top,
+                right,
+                bottom,
+            })
+        } else {
+            None
+        }
+    }
+    /// How far ``other`` starts to the right of this box; negative if they overlap.
+    pub fn horizontal_gap(&self, other: &BBox) -> i64 {
+        i64::from(other.left) - i64::from(self.right)
+    }
+    /// How far ``other`` starts below this box; negative if they overlap.
+    pub fn vertical_gap(&self, other: &BBox) -> i64 {
+        i64::from(other.top) - i64::from(self.bottom)
+    }
+}
+
+impl WordCoords {
+    /// The normalized bounding box of this word.
+    pub fn bbox(&self) -> BBox {
+        BBox::new(
+            u32::from(self.x1),
+            u32::from(self.y1),
+            u32::from(self.x2),
+            u32::from(self.y2),
+        )
+    }
+}
+
+impl BookWord {
+    pub fn bbox(&self) -> BBox {
+        self.coords.bbox()
+    }
+}
+
+/// The bounding box of a whole line, or ``None`` for an empty line.
+pub fn line_bbox(line: &[BookWord]) -> Option<BBox> {
+    line.iter()
+        .map(|w| w.bbox())
+        .fold(None, |acc: Option<BBox>, b| match acc {
+            None => Some(b),
+            Some(a) => Some(a.union(&b)),
+        })
+}
+
+/// The baseline of a line: the lowest ``base`` of its words.
+pub fn line_baseline(line: &[BookWord]) -> Option<u32> {
+    line.iter().map(|w| u32::from(w.coords.base)).max()
+}
+
+/// Horizontal space between each pair of consecutive words on a line.
+pub fn word_gaps(line: &[BookWord]) -> Vec<i64> {
+    line.windows(2)
+        .map(|pair| pair[0].bbox().horizontal_gap(&pair[1].bbox()))
+        .collect()
+}
+
+/// Distance between the baselines of each pair of consecutive non-empty lines.
+pub fn line_spacing(lines: &[Vec<BookWord>]) -> Vec<i64> {
+    let baselines: Vec<i64> = lines
+        .iter()
+        .filter_map(|l| line_baseline(l))
+        .map(i64::from)
+        .collect();
+    baselines.windows(2).map(|pair| pair[1] - pair[0]).collect()
+}
+
+impl BookPage {
+    /// Convert a length in page pixels to inches, if we know the page's DPI.
+    pub fn to_inches(&self, pixels: f64) -> Option<f64> {
+        if self.dpi == 0 {
+            None
+        } else {
+            Some(pixels / f64::from(self.dpi))
+        }
+    }
+    /// Convert a length in page pixels to typographic points, if we know the page's DPI.
+    pub fn to_points(&self, pixels: f64) -> Option<f64> {
+        self.to_inches(pixels).map(|inches| inches * POINTS_PER_INCH)
+    }
+    /// The bounding box of all the text on the page, or ``None`` if it is blank.
+    pub fn text_bbox(&self) -> Option<BBox> {
+        self.lines
+            .iter()
+            .filter_map(|l| line_bbox(l))
+            .fold(None, |acc: Option<BBox>, b| match acc {
+                None => Some(b),
+                Some(a) => Some(a.union(&b)),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // From coords.rs: the XML order is (x1,y2,x2,y1,base), so y2 is the bottom of the word.
+    fn word(x1: u16, y2: u16, x2: u16, y1: u16, base: u16, text: &str) -> BookWord {
+        BookWord::new(
+            WordCoords {
+                x1,
+                y1,
+                x2,
+                y2,
+                base,
+            },
+            text.to_owned(),
+        )
+    }
+
+    fn the_tragedy() -> Vec<BookWord> {
+        vec![
+            word(814, 1248, 1012, 1168, 1247, "THE"),
+            word(1068, 1250, 1548, 1168, 1248, "TRAGEDY"),
+        ]
+    }
+
+    #[test]
+    fn normalized_bbox() {
+        let the = &the_tragedy()[0];
+        let b = the.bbox();
+        assert_eq!(BBox::new(814, 1168, 1012, 1248), b);
+        assert_eq!(198, b.width());
+        assert_eq!(80, b.height());
+
+        // Swapped orderings normalize to the same box.
+        let flipped = word(1012, 1168, 814, 1248, 1247, "THE");
+        assert_eq!(b, flipped.bbox());
+    }
+
+    #[test]
+    fn lines_and_gaps() {
+        let line = the_tragedy();
+        assert_eq!(Some(BBox::new(814, 1168, 1548, 1250)), line_bbox(&line));
+        assert_eq!(vec![56], word_gaps(&line));
+        assert_eq!(None, line_bbox(&[]));
+
+        let music = vec![word(1329, 2598, 1495, 2551, 2598, "Music")];
+        let lines = vec![line, Vec::new(), music];
+        assert_eq!(vec![2598 - 1248], line_spacing(&lines));
+    }
+
+    #[test]
+    fn union_and_intersection() {
+        let a = BBox::new(0, 0, 10, 10);
+        let b = BBox::new(5, 5, 20, 20);
+        assert_eq!(BBox::new(0, 0, 20, 20), a.union(&b));
+        assert_eq!(Some(BBox::new(5, 5, 10, 10)), a.intersection(&b));
+        assert_eq!(None, a.intersection(&BBox::new(10, 0, 20, 10)));
+        assert_eq!(-5, a.horizontal_gap(&b));
+    }
+
+    #[test]
+    fn physical_units() {
+        let mut page = BookPage::new();
+        assert_eq!(None, page.to_inches(600.0));
+        page.dpi = 600;
+        assert_eq!(Some(1.0), page.to_inches(600.0));
+        assert_eq!(Some(72.0), page.to_points(600.0));
+        page.lines = vec![the_tragedy()];
+        assert_eq!(Some(BBox::new(814, 1168, 1548, 1250)), page.text_bbox());
+    }
+}
@@ -0,0 +1,385 @@
+use coords::CoordsRepair;
+use geometry::{line_baseline, line_bbox};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use std::io::{self, BufRead, Write};
+use types::{Book, BookPage, RichDjVu, WordCoords};
+use writer::escape;
+
+/// Scores are written with this many decimals.
+const SCORE_DIGITS: usize = 4;
+
+fn write_page<W: Write>(
+    page: &BookPage,
+    number: usize,
+    score: Option<f64>,
+    out: &mut W,
+) -> io::Result<()> {
+    write!(
+        out,
+        "<div class=\"ocr_page\" id=\"page_{}\" title=\"bbox 0 0 {} {}; ppageno {}",
+        number + 1,
+        page.width,
+        page.height,
+        number
+    )?;
+    if page.dpi > 0 {
+        write!(out, "; scan_res {} {}", page.dpi, page.dpi)?;
+    }
+    if let Some(score) = score {
+        write!(out, "; x_poetry_score {:.*}", SCORE_DIGITS, score)?;
+    }
+    writeln!(out, "\">")?;
+    for (l, line) in page.lines.iter().enumerate() {
+        let bbox = match line_bbox(line) {
+            Some(bbox) => bbox,
+            None => continue,
+        };
+        // hOCR baselines are offsets from the bottom of the line.
+        let offset = line_baseline(line)
+            .map(|base| i64::from(base) - i64::from(bbox.bottom))
+            .unwrap_or(0);
+        write!(
+            out,
+            "<span class=\"ocr_line\" id=\"line_{}_{}\" title=\"bbox {} {} {} {}; baseline 0 {}\">",
+            number + 1,
+            l + 1,
+            bbox.left,
+            bbox.top,
+            bbox.right,
+            bbox.bottom,
+            offset
+        )?;
+        for (w, word) in line.iter().enumerate() {
+            let b = word.bbox();
+            if w > 0 {
+                write!(out, " ")?;
+            }
+            write!(
+                out,
+                "<span class=\"ocrx_word\" id=\"word_{}_{}_{}\" title=\"bbox {} {} {} {}\">{}</span>",
+                number + 1,
+                l + 1,
+                w + 1,
+                b.left,
+                b.top,
+                b.right,
+                b.bottom,
+                escape(&word.text)
+            )?;
+        }
+        writeln!(out, "</span>")?;
+    }
+    writeln!(out, "</div>")
+}
+
+/// Write a book as one hOCR document, for OCR viewers and digital-library tools.
+///
+/// Each page becomes an ``ocr_page`` with its size and resolution, each line an ``ocr_line``
+/// with its baseline, and each word an ``ocrx_word`` with its bounding box; empty lines are
+/// left out. ``scores`` holds a poetry score per page, written as the ``x_poetry_score``
+/// property of the page; pages past its end, or with ``None``, get none.
+pub fn write_hocr<W: Write>(book: &Book, scores: &[Option<f64>], out: &mut W) -> io::Result<()> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        out,
+        "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\">"
+    )?;
+    writeln!(
+        out,
+        "<html xmlns=\"http://www.w3.org/1999/xhtml\">\n<head>\n<title></title>"
+    )?;
+    writeln!(
+        out,
+        "<meta http-equiv=\"Content-Type\" content=\"text/html;charset=utf-8\" />"
+    )?;
+    writeln!(out, "<meta name=\"ocr-system\" content=\"djvuxml\" />")?;
+    writeln!(
+        out,
+        "<meta name=\"ocr-capabilities\" content=\"ocr_page ocr_line ocrx_word\" />"
+    )?;
+    writeln!(out, "</head>\n<body>")?;
+    for (i, page) in book.pages.iter().enumerate() {
+        write_page(page, i, scores.get(i).cloned().unwrap_or(None), out)?;
+    }
+    writeln!(out, "</body>\n</html>")
+}
+
+/// Classes of the hOCR elements that hold a line of words; Tesseract uses all of these.
+const LINE_CLASSES: &[&str] = &[
+    "ocr_line",
+    "ocrx_line",
+    "ocr_header",
+    "ocr_caption",
+    "ocr_textfloat",
+];
+
+/// What an open element of an hOCR document is to us.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Page,
+    Line,
+    Word,
+    Other,
+}
+
+/// The properties we use from an hOCR ``title`` attribute.
+#[derive(Debug, Default, PartialEq)]
+struct Title {
+    /// Left, top, right, bottom.
+    bbox: Option<[u32; 4]>,
+    /// Offset of the baseline from the bottom of the box; we ignore its slope.
+    baseline: Option<i64>,
+    scan_res: Option<u32>,
+}
+
+/// Parse ``bbox 0 0 2000 3000; scan_res 300 300; ...``, ignoring anything we do not use.
+fn parse_title(title: &str) -> Title {
+    let mut found = Title::default();
+    for property in title.split(';') {
+        let mut fields = property.split_whitespace();
+        let key = fields.next();
+        let numbers: Vec<f64> = fields.filter_map(|f| f.parse().ok()).collect();
+        match key {
+            Some("bbox") if numbers.len() == 4 && numbers.iter().all(|&n| n >= 0.0) => {
+                found.bbox = Some([
+                    numbers[0].round() as u32,
+                    numbers[1].round() as u32,
+                    numbers[2].round() as u32,
+                    numbers[3].round() as u32,
+                ])
+            }
+            Some("baseline") if numbers.len() == 2 => {
+                found.baseline = Some(numbers[1].round() as i64)
+            }
+            Some("scan_res") if !numbers.is_empty() && numbers[0] > 0.0 => {
+                found.scan_res = Some(numbers[0].round() as u32)
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+fn attribute(e: &BytesStart, key: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|attr| attr.key == key)
+        .map(|attr| match attr.unescaped_value() {
+            Ok(value) => String::from_utf8_lossy(&value).into_owned(),
+            Err(_) => String::from_utf8_lossy(&attr.value).into_owned(),
+        })
+}
+
+fn kind(e: &BytesStart) -> Kind {
+    let class = attribute(e, b"class").unwrap_or_default();
+    let classes: Vec<&str> = class.split_whitespace().collect();
+    if classes.contains(&"ocr_page") {
+        Kind::Page
+    } else if classes.iter().any(|c| LINE_CLASSES.contains(c)) {
+        Kind::Line
+    } else if classes.contains(&"ocrx_word") || classes.contains(&"ocr_word") {
+        Kind::Word
+    } else {
+        Kind::Other
+    }
+}
+
+fn to_u16(x: u32) -> Option<u16> {
+    if x > u32::from(u16::MAX) {
+        None
+    } else {
+        Some(x as u16)
+    }
+}
+
+/// Coordinates of a word from its ``bbox``, with its line's baseline if there is one.
+fn word_coords(bbox: [u32; 4], base: Option<u32>) -> Option<WordCoords> {
+    Some(WordCoords {
+        x1: to_u16(bbox[0])?,
+        y1: to_u16(bbox[1])?,
+        x2: to_u16(bbox[2])?,
+        y2: to_u16(bbox[3])?,
+        base: to_u16(base.unwrap_or(bbox[3]))?,
+    })
+}
+
+/// Parse an hOCR document into the same events as ``parse_rich_xml``.
+///
+/// Pages are ``ocr_page`` elements, sized by their ``bbox`` with ``scan_res`` as their DPI;
+/// lines are ``ocr_line`` (or the other line classes Tesseract writes), with word baselines
+/// from the line's ``baseline``; words are ``ocrx_word`` elements, whose text may be wrapped
+/// in formatting tags. Words without a usable ``bbox`` come without coordinates.
+pub fn parse_hocr<R, F>(reader: R, mut callback: F)
+where
+    R: BufRead,
+    F: FnMut(RichDjVu),
+{
+    let mut xml = Reader::from_reader(reader);
+    xml.check_end_names(false);
+    xml.expand_empty_elements(true);
+    let mut buf = Vec::new();
+    let mut open: Vec<Kind> = Vec::new();
+    let mut baseline: Option<u32> = None;
+    let mut word: Option<(Option<WordCoords>, String)> = None;
+    loop {
+        match xml.read_event(&mut buf) {
+            Ok(Event::Eof) => break,
+            Ok(Event::Start(ref e)) => {
+                let kind = if word.is_some() { Kind::Other } else { kind(e) };
+                let title = parse_title(&attribute(e, b"title").unwrap_or_default());
+                match kind {
+                    Kind::Page => {
+                        callback(RichDjVu::StartPage);
+                        if let Some(b) = title.bbox {
+                            callback(RichDjVu::PageDimensions(b[2], b[3]));
+                        }
+                        if let Some(dpi) = title.scan_res {
+                            callback(RichDjVu::PageDPI(dpi));
+                        }
+                    }
+                    Kind::Line => {
+                        callback(RichDjVu::StartLine);
+                        baseline = match (title.bbox, title.baseline) {
+                            (Some(b), Some(offset)) if i64::from(b[3]) + offset >= 0 => {
+                                Some((i64::from(b[3]) + offset) as u32)
+                            }
+                            _ => None,
+                        };
+                    }
+                    Kind::Word => {
+                        let coords = title.bbox.and_then(|b| word_coords(b, baseline));
+                        word = Some((coords, String::new()));
+                    }
+                    Kind::Other => {}
+                }
+                open.push(kind);
+            }
+            Ok(Event::Text(ref e)) => {
+                if let Some((_, ref mut text)) = word {
+                    match e.unescape_and_decode(&xml) {
+                        Ok(t) => text.push_str(&t),
+                        Err(_) => text.push_str(&String::from_utf8_lossy(e)),
+                    }
+                }
+            }
+            Ok(Event::End(_)) => match open.pop() {
+                Some(Kind::Page) => callback(RichDjVu::EndPage),
+                Some(Kind::Line) => {
+                    baseline = None;
+                    callback(RichDjVu::EndLine)
+                }
+                Some(Kind::Word) => {
+                    if let Some((coords, text)) = word.take() {
+                        let text = text.trim();
+                        if !text.is_empty() {
+                            callback(RichDjVu::Word(coords, text.to_owned()));
+                        }
+                    }
+                }
+                _ => {}
+            },
+            Err(e) => {
+                callback(RichDjVu::Error(format!("{:?}", e)));
+                break;
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+/// Collect a whole hOCR document into a ``Book``, like ``process_book`` does for DjVu XML.
+pub fn process_hocr<R: BufRead>(reader: R) -> Result<Book, String> {
+    ::book_from_events(
+        |callback| parse_hocr(reader, callback),
+        &CoordsRepair::default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<OBJECT height="3000" width="2000"><PARAM name="DPI" value="600"/>
+<LINE><WORD coords="814,1248,1012,1168,1240">Fish</WORD><WORD coords="1030,1250,1300,1170,1240">&amp;</WORD><WORD coords="1330,1250,1500,1170,1240">Chips</WORD></LINE>
+<LINE></LINE>
+</OBJECT>
+<OBJECT height="3000" width="2000"><PARAM name="DPI" value="600"/></OBJECT>"#;
+
+    #[test]
+    fn writes_pages_lines_and_words() {
+        let book = ::process_book(XML.as_bytes()).unwrap();
+        let mut out = Vec::new();
+        write_hocr(&book, &[Some(0.91234)], &mut out).unwrap();
+        let hocr = String::from_utf8(out).unwrap();
+        assert!(hocr.contains(
+            "<div class=\"ocr_page\" id=\"page_1\" title=\"bbox 0 0 2000 3000; ppageno 0; scan_res 600 600; x_poetry_score 0.9123\">"
+        ));
+        assert!(hocr.contains("title=\"bbox 814 1168 1500 1250; baseline 0 -10\">"));
+        assert!(hocr.contains("title=\"bbox 1030 1170 1300 1250\">&amp;</span>"));
+        assert_eq!(1, hocr.matches("class=\"ocr_line\"").count());
+        assert!(hocr
+            .contains("id=\"page_2\" title=\"bbox 0 0 2000 3000; ppageno 1; scan_res 600 600\">"));
+    }
+
+    const HOCR: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml"><head><title></title></head><body>
+<div class="ocr_page" id="page_1" title='image "p1.png"; bbox 0 0 2480 3508; ppageno 0; scan_res 300 300'>
+<div class="ocr_carea"><p class="ocr_par">
+<span class="ocr_line" id="line_1_1" title="bbox 200 300 900 360; baseline 0.001 -12; x_size 50">
+<span class="ocrx_word" title="bbox 200 300 420 360; x_wconf 93"><strong>Season</strong></span>
+<span class="ocrx_word" title="bbox 450 310 520 360; x_wconf 95">of&amp;</span>
+<span class="ocrx_word" title="x_wconf 12">mists</span>
+</span>
+<span class="ocr_header" title="bbox 200 100 300 140"><span class="ocrx_word" title="bbox 200 100 300 140">40</span></span>
+</p></div></div>
+<div class="ocr_page" title="bbox 0 0 2480 3508"></div>
+</body></html>"#;
+
+    #[test]
+    fn titles() {
+        assert_eq!(
+            Title {
+                bbox: Some([0, 0, 2480, 3508]),
+                baseline: Some(-12),
+                scan_res: Some(300),
+            },
+            parse_title("bbox 0 0 2480 3508; baseline 0.001 -12; scan_res 300 300")
+        );
+        assert_eq!(Title::default(), parse_title("image \"p1.png\"; bbox 1 2"));
+    }
+
+    #[test]
+    fn reads_pages_lines_and_words() {
+        let book = process_hocr(HOCR.as_bytes()).unwrap();
+        assert_eq!(2, book.pages.len());
+        let page = &book.pages[0];
+        assert_eq!((2480, 3508, 300), (page.width, page.height, page.dpi));
+        assert_eq!(2, page.lines.len());
+        assert_eq!("40\nSeason\tof&\tmists\n", page.text());
+        assert_eq!(
+            WordCoords {
+                x1: 200,
+                y1: 300,
+                x2: 420,
+                y2: 360,
+                base: 348,
+            },
+            page.lines[0][0].coords
+        );
+        // "mists" had no bbox, so it was placed after "of&".
+        assert_eq!(1, book.coords.inferred);
+        assert_eq!(140, page.lines[1][0].coords.base);
+        assert_eq!(0, book.pages[1].dpi);
+    }
+
+    #[test]
+    fn round_trip() {
+        let book = process_hocr(HOCR.as_bytes()).unwrap();
+        let mut written = Vec::new();
+        write_hocr(&book, &[], &mut written).unwrap();
+        let reread = process_hocr(&written[..]).unwrap();
+        assert_eq!(book.pages, reread.pages);
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use text::{tokenize_with, Normalizer};
+use types::{Book, BookPage};
+
+/// Profiles are built from character sequences of this length.
+const NGRAM: usize = 3;
+/// Keep this many of the most frequent n-grams of each sample text.
+const PROFILE_SIZE: usize = 400;
+/// Texts with fewer n-grams than this are too short to guess at.
+const MIN_NGRAMS: usize = 50;
+/// A guess on a short text must be this far ahead of the runner-up (see
+/// ``LanguageGuess::confidence``); the bar rises with length up to ``LONG_MIN_CONFIDENCE``.
+/// Languages we have no profile for, like Spanish or Portuguese, resemble two or more of ours
+/// about equally, so they fall short and are left unidentified.
+const MIN_CONFIDENCE: f64 = 0.32;
+/// The lead a guess needs once it has ``LONG_NGRAMS`` n-grams. A stanza is too short for its
+/// language to stand far out; a long unsupported text can drift towards one of our profiles.
+const LONG_MIN_CONFIDENCE: f64 = 0.36;
+const LONG_NGRAMS: usize = 400;
+
+/// The languages we have profiles for, as ISO 639-1 codes, with their sample texts.
+const SAMPLES: &[(&str, &str)] = &[
+    ("en", include_str!("lang/english.txt")),
+    ("fr", include_str!("lang/french.txt")),
+    ("de", include_str!("lang/german.txt")),
+    ("la", include_str!("lang/latin.txt")),
+];
+
+/// The most likely language of a text, and how far ahead of the runner-up it is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LanguageGuess {
+    /// An ISO 639-1 code: one of ``languages()``.
+    pub language: String,
+    /// ``1 - second/best`` of the profile similarities: 0 is a tie, 1 is certain.
+    pub confidence: f64,
+}
+
+/// Every language ``identify`` can return.
+pub fn languages() -> Vec<&'static str> {
+    SAMPLES.iter().map(|&(code, _)| code).collect()
+}
+
+/// Character n-gram counts of the words of a text, each word padded with a space on either
+/// side so that beginnings and endings count. Numbers are skipped.
+#[derive(Debug, Default, Clone)]
+pub struct NgramCounts {
+    counts: HashMap<String, f64>,
+    total: usize,
+}
+
+impl NgramCounts {
+    pub fn new() -> NgramCounts {
+        NgramCounts::default()
+    }
+    pub fn add_text(&mut self, text: &str) {
+        self.add_text_with(&Normalizer::all(), text)
+    }
+    /// ``add_text``, tokenizing after some other cleanup.
+    pub fn add_text_with(&mut self, normalizer: &Normalizer, text: &str) {
+        for token in tokenize_with(normalizer, text) {
+            if token.chars().any(|c| c.is_numeric()) {
+                continue;
+            }
+            let padded: Vec<char> = Some(' ')
+                .into_iter()
+                .chain(token.chars())
+                .chain(Some(' '))
+                .collect();
+            for window in padded.windows(NGRAM) {
+                *self.counts.entry(window.iter().collect()).or_insert(0.0) += 1.0;
+                self.total += 1;
+            }
+        }
+    }
+    fn norm(&self) -> f64 {
+        self.counts.values().map(|c| c * c).sum::<f64>().sqrt()
+    }
+}
+
+/// The most frequent n-grams of a language, as a unit vector.
+struct Profile {
+    language: &'static str,
+    weights: HashMap<String, f64>,
+}
+
+impl Profile {
+    fn new(language: &'static str, sample: &str) -> Profile {
+        let mut counts = NgramCounts::new();
+        counts.add_text(sample);
+        let mut ranked: Vec<(String, f64)> = counts.counts.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(PROFILE_SIZE);
+        let norm = ranked.iter().map(|&(_, c)| c * c).sum::<f64>().sqrt();
+        Profile {
+            language,
+            weights: ranked.into_iter().map(|(g, c)| (g, c / norm)).collect(),
+        }
+    }
+    /// Cosine similarity with some counts.
+    fn similarity(&self, counts: &NgramCounts, norm: f64) -> f64 {
+        let dot: f64 = self
+            .weights
+            .iter()
+            .filter_map(|(g, w)| counts.counts.get(g).map(|c| c * w))
+            .sum();
+        dot / norm
+    }
+}
+
+lazy_static! {
+    static ref PROFILES: Vec<Profile> = SAMPLES
+        .iter()
+        .map(|&(language, sample)| Profile::new(language, sample))
+        .collect();
+}
+
+/// Guess the language of some n-gram counts, if there are enough of them and one of our
+/// languages stands out.
+pub fn identify_counts(counts: &NgramCounts) -> Option<LanguageGuess> {
+    if counts.total < MIN_NGRAMS {
+        return None;
+    }
+    let norm = counts.norm();
+    let mut scores: Vec<(&'static str, f64)> = PROFILES
+        .iter()
+        .map(|p| (p.language, p.similarity(counts, norm)))
+        .collect();
+    scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    let (language, best) = scores[0];
+    if best <= 0.0 {
+        return None;
+    }
+    let confidence = 1.0 - scores[1].1 / best;
+    let length = (counts.total as f64 / LONG_NGRAMS as f64).min(1.0);
+    if confidence < MIN_CONFIDENCE + (LONG_MIN_CONFIDENCE - MIN_CONFIDENCE) * length {
+        return None;
+    }
+    Some(LanguageGuess {
+        language: language.to_owned(),
+        confidence,
+    })
+}
+
+/// Guess the language of a text.
+pub fn identify(text: &str) -> Option<LanguageGuess> {
+    let mut counts = NgramCounts::new();
+    counts.add_text(text);
+    identify_counts(&counts)
+}
+
+fn add_page(counts: &mut NgramCounts, page: &BookPage, normalizer: &Normalizer) {
+    for word in page.lines.iter().flat_map(|l| l.iter()) {
+        counts.add_text_with(normalizer, &word.text);
+    }
+}
+
+/// Guess the language of one page.
+pub fn identify_page(page: &BookPage) -> Option<LanguageGuess> {
+    identify_page_with(page, &Normalizer::all())
+}
+
+/// ``identify_page``, tokenizing after some other cleanup.
+pub fn identify_page_with(page: &BookPage, normalizer: &Normalizer) -> Option<LanguageGuess> {
+    let mut counts = NgramCounts::new();
+    add_page(&mut counts, page, normalizer);
+    identify_counts(&counts)
+}
+
+/// Guess the language of a whole book from the text of all of its pages.
+pub fn identify_book(book: &Book) -> Option<LanguageGuess> {
+    identify_book_with(book, &Normalizer::all())
+}
+
+/// ``identify_book``, tokenizing after some other cleanup.
+pub fn identify_book_with(book: &Book, normalizer: &Normalizer) -> Option<LanguageGuess> {
+    let mut counts = NgramCounts::new();
+    for page in &book.pages {
+        add_page(&mut counts, page, normalizer);
+    }
+    identify_counts(&counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn language(text: &str) -> String {
+        identify(text).unwrap().language
+    }
+
+    #[test]
+    fn identifies_languages() {
+        assert_eq!(
+            "en",
+            language(
+                "Shall I compare thee to a summer's day? Thou art more lovely and more \
+                 temperate: rough winds do shake the darling buds of May, and summer's lease \
+                 hath all too short a date."
+            )
+        );
+        assert_eq!(
+            "fr",
+            language(
+                "Demain, dès l'aube, à l'heure où blanchit la campagne, je partirai. Vois-tu, \
+                 je sais que tu m'attends. J'irai par la forêt, j'irai par la montagne."
+            )
+        );
+        assert_eq!(
+            "de",
+            language(
+                "Wer reitet so spät durch Nacht und Wind? Es ist der Vater mit seinem Kind; er \
+                 hat den Knaben wohl in dem Arm, er fasst ihn sicher, er hält ihn warm."
+            )
+        );
+        assert_eq!(
+            "la",
+            language(
+                "Odi et amo. Quare id faciam, fortasse requiris. Nescio, sed fieri sentio et \
+                 excrucior. Vivamus, mea Lesbia, atque amemus, rumoresque senum severiorum \
+                 omnes unius aestimemus assis."
+            )
+        );
+    }
+
+    #[test]
+    fn short_stanzas() {
+        assert_eq!(
+            "en",
+            language(
+                "When, in disgrace with fortune and men's eyes,\n\
+                 I all alone beweep my outcast state,\n\
+                 And trouble deaf heaven with my bootless cries,\n\
+                 And look upon myself and curse my fate,"
+            )
+        );
+        assert_eq!(
+            "en",
+            language(
+                "Tyger Tyger, burning bright,\nIn the forests of the night;\n\
+                 What immortal hand or eye,\nCould frame thy fearful symmetry?"
+            )
+        );
+    }
+
+    #[test]
+    fn too_short_to_tell() {
+        assert_eq!(None, identify("The end."));
+        assert_eq!(
+            None,
+            identify("1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20")
+        );
+        let guess = identify(include_str!("lang/english.txt")).unwrap();
+        assert!(guess.confidence > 0.0 && guess.confidence <= 1.0);
+    }
+
+    #[test]
+    fn unsupported_languages() {
+        assert_eq!(
+            None,
+            identify(
+                "En un lugar de la Mancha, de cuyo nombre no quiero acordarme, no ha mucho \
+                 tiempo que vivía un hidalgo de los de lanza en astillero, adarga antigua, \
+                 rocín flaco y galgo corredor."
+            )
+        );
+        assert_eq!(
+            None,
+            identify(
+                "As armas e os barões assinalados que da ocidental praia Lusitana por mares \
+                 nunca dantes navegados passaram ainda além da Taprobana, em perigos e guerras \
+                 esforçados."
+            )
+        );
+    }
+}
@@ -0,0 +1,367 @@
+use geometry::{line_bbox, BBox};
+use types::{BookPage, BookWord};
+
+/// Lines wider than this fraction of the text block are treated as spanning every column.
+const SPANNING_FRACTION: f64 = 0.6;
+/// A gutter between columns must be at least this fraction of the text block's width.
+const MIN_GUTTER_FRACTION: f64 = 0.02;
+/// Clusters of fewer lines than this are not columns (page numbers, marginal notes).
+const MIN_COLUMN_LINES: usize = 3;
+/// At most this fraction of the lines may cross a gutter, like a centered title or a running
+/// header that is not quite wide enough to span the columns.
+const MAX_CROSSING_FRACTION: f64 = 0.2;
+/// A page is rotated when more than this fraction of its lines of two or more words are
+/// taller than they are wide.
+const ROTATED_FRACTION: f64 = 0.5;
+
+/// Lines that cross the gutters between columns, as indices into ``lines``.
+///
+/// A gutter is the stretch of a low-coverage strip of the page (crossed by at most
+/// ``MAX_CROSSING_FRACTION`` of the lines) that the fewest lines cross, with at least
+/// ``MIN_COLUMN_LINES`` lines entirely on either side of it.
+fn gutter_crossings(lines: &[BBox], min_gutter: u32) -> Vec<usize> {
+    let max_crossings = (lines.len() as f64 * MAX_CROSSING_FRACTION) as usize;
+    let mut edges: Vec<u32> = lines.iter().flat_map(|b| vec![b.left, b.right]).collect();
+    edges.sort();
+    edges.dedup();
+    // (left, right, how many lines cross it) for each strip between two edges.
+    let strips: Vec<(u32, u32, usize)> = edges
+        .windows(2)
+        .map(|w| {
+            let crossing = lines
+                .iter()
+                .filter(|b| b.left <= w[0] && b.right >= w[1])
+                .count();
+            (w[0], w[1], crossing)
+        })
+        .collect();
+
+    let mut crossers = Vec::new();
+    for run in strips.split(|s| s.2 > max_crossings) {
+        let fewest = match run.iter().map(|s| s.2).min() {
+            Some(fewest) => fewest,
+            None => continue,
+        };
+        // The widest stretch of the run that only the fewest lines cross.
+        let gutter = run
+            .split(|s| s.2 != fewest)
+            .filter(|stretch| !stretch.is_empty())
+            .map(|stretch| (stretch[0].0, stretch[stretch.len() - 1].1))
+            .max_by_key(|&(left, right)| right - left);
+        let (left, right) = match gutter {
+            Some(gutter) => gutter,
+            None => continue,
+        };
+        let before = lines.iter().filter(|b| b.right <= left).count();
+        let after = lines.iter().filter(|b| b.left >= right).count();
+        if right - left < min_gutter || before < MIN_COLUMN_LINES || after < MIN_COLUMN_LINES {
+            continue;
+        }
+        crossers.extend(
+            lines
+                .iter()
+                .enumerate()
+                .filter(|&(_, b)| b.left < right && b.right > left)
+                .map(|(i, _)| i),
+        );
+    }
+    crossers
+}
+
+/// Find the columns of a page from the bounding boxes of its lines.
+///
+/// Lines that are not too wide are clustered by their horizontal extent: any vertical strip
+/// of the page that none of them cross is a gutter. The few lines that cross a gutter, such
+/// as a centered title, are left out first, so that they don't join the columns into one.
+/// Returns the ``(left, right)`` extent of each column from left to right, or nothing if
+/// the page is a single column.
+pub fn detect_columns(lines: &[BBox]) -> Vec<(u32, u32)> {
+    let text = match lines.first() {
+        Some(first) => lines.iter().fold(*first, |acc, b| acc.union(b)),
+        None => return Vec::new(),
+    };
+    let width = f64::from(text.width());
+    let min_gutter = (width * MIN_GUTTER_FRACTION) as u32;
+
+    let narrow: Vec<BBox> = lines
+        .iter()
+        .filter(|b| f64::from(b.width()) < width * SPANNING_FRACTION)
+        .cloned()
+        .collect();
+    let crossers = gutter_crossings(&narrow, min_gutter);
+    let mut narrow: Vec<BBox> = narrow
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !crossers.contains(i))
+        .map(|(_, b)| b)
+        .collect();
+    narrow.sort_by_key(|b| b.left);
+
+    // (left, right, number of lines)
+    let mut clusters: Vec<(u32, u32, usize)> = Vec::new();
+    for b in narrow {
+        if let Some(last) = clusters.last_mut() {
+            if b.left <= last.1 + min_gutter {
+                last.1 = last.1.max(b.right);
+                last.2 += 1;
+                continue;
+            }
+        }
+        clusters.push((b.left, b.right, 1));
+    }
+
+    let columns: Vec<(u32, u32)> = clusters
+        .into_iter()
+        .filter(|c| c.2 >= MIN_COLUMN_LINES)
+        .map(|c| (c.0, c.1))
+        .collect();
+    if columns.len() < 2 {
+        Vec::new()
+    } else {
+        columns
+    }
+}
+
+/// Is this page turned on its side, as when a wide table or a landscape plate is printed
+/// sideways? Its lines then run down the page, so most of them are taller than they are wide.
+pub fn is_rotated(page: &BookPage) -> bool {
+    let boxes: Vec<BBox> = page
+        .lines
+        .iter()
+        .filter(|l| l.len() >= 2)
+        .filter_map(|l| line_bbox(l))
+        .collect();
+    let tall = boxes.iter().filter(|b| b.height() > b.width()).count();
+    !boxes.is_empty() && tall as f64 > boxes.len() as f64 * ROTATED_FRACTION
+}
+
+/// A copy of ``page`` with its words and lines in reading order.
+///
+/// The XML gives lines in whatever order the OCR engine emitted them, which is wrong for
+/// multi-column pages. Here, words are sorted left to right within each line, and lines are
+/// read top to bottom within each column, columns left to right. A line that spans the
+/// columns (a title, a running header) ends one band of columns and starts the next.
+/// Empty lines have no position, so each stays right after the line it followed in the XML.
+///
+/// Coordinates alone cannot tell whether a rotated page (see ``is_rotated``) was turned
+/// clockwise or counterclockwise, so which way its lines read is unknown; such a page is
+/// left in XML order.
+pub fn reading_order(page: &BookPage) -> BookPage {
+    if is_rotated(page) {
+        return page.clone();
+    }
+    // (original index, extent, words)
+    let mut lines: Vec<(usize, BBox, Vec<BookWord>)> = page
+        .lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, l)| {
+            let mut words = l.clone();
+            words.sort_by_key(|w| w.bbox().left);
+            line_bbox(&words).map(|b| (i, b, words))
+        })
+        .collect();
+    lines.sort_by_key(|&(_, b, _)| (b.top, b.left));
+
+    let boxes: Vec<BBox> = lines.iter().map(|&(_, b, _)| b).collect();
+    let columns = detect_columns(&boxes);
+
+    let mut ordered = Vec::with_capacity(lines.len());
+    let mut band: Vec<Vec<(usize, Vec<BookWord>)>> = vec![Vec::new(); columns.len()];
+    for (i, b, words) in lines {
+        match columns
+            .iter()
+            .position(|&(left, right)| b.left >= left && b.right <= right)
+        {
+            Some(c) => band[c].push((i, words)),
+            None => {
+                for column in band.iter_mut() {
+                    ordered.append(column);
+                }
+                ordered.push((i, words));
+            }
+        }
+    }
+    for column in band.iter_mut() {
+        ordered.append(column);
+    }
+
+    // Put empty lines back after the line they followed, or first if nothing came before.
+    let empties_after = |i: usize| {
+        (i..page.lines.len())
+            .take_while(move |&j| page.lines[j].is_empty())
+            .map(|j| (j, Vec::new()))
+    };
+    let mut restored: Vec<(usize, Vec<BookWord>)> = empties_after(0).collect();
+    for (i, words) in ordered {
+        restored.push((i, words));
+        restored.extend(empties_after(i + 1));
+    }
+    let ordered = restored;
+
+    // Line marks refer to positions in ``lines``, so they move with their lines.
+    let moved = |marks: &[usize]| -> Vec<usize> {
+        ordered
+            .iter()
+            .enumerate()
+            .filter(|&(_, &(i, _))| marks.contains(&i))
+            .map(|(new, _)| new)
+            .collect()
+    };
+    let header_lines = moved(&page.header_lines);
+    let footer_lines = moved(&page.footer_lines);
+
+    BookPage {
+        lines: ordered.into_iter().map(|(_, words)| words).collect(),
+        header_lines,
+        footer_lines,
+        ..page.clone()
+    }
+}
+
+impl BookPage {
+    /// See ``layout::reading_order``.
+    pub fn in_reading_order(&self) -> BookPage {
+        reading_order(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::WordCoords;
+
+    fn word(x1: u16, y1: u16, x2: u16, y2: u16, text: &str) -> BookWord {
+        BookWord::new(
+            WordCoords {
+                x1,
+                y1,
+                x2,
+                y2,
+                base: y2,
+            },
+            text.to_owned(),
+        )
+    }
+
+    fn text(page: &BookPage) -> Vec<String> {
+        page.lines
+            .iter()
+            .map(|l| {
+                l.iter()
+                    .map(|w| w.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn words_sorted_within_line() {
+        let mut page = BookPage::new();
+        page.lines = vec![vec![
+            word(300, 10, 400, 50, "world"),
+            word(100, 10, 200, 50, "hello"),
+        ]];
+        assert_eq!(vec!["hello world"], text(&reading_order(&page)));
+    }
+
+    #[test]
+    fn two_columns_with_title() {
+        let mut page = BookPage::new();
+        // XML order interleaves the columns row by row.
+        page.lines = vec![
+            vec![word(100, 0, 1900, 50, "TITLE")],
+            vec![word(100, 100, 900, 150, "L1")],
+            vec![word(1100, 100, 1900, 150, "R1")],
+            vec![word(100, 200, 900, 250, "L2")],
+            vec![word(1100, 200, 1900, 250, "R2")],
+            vec![word(100, 300, 800, 350, "L3")],
+            vec![word(1100, 300, 1800, 350, "R3")],
+            vec![],
+        ];
+        assert_eq!(
+            vec!["TITLE", "L1", "L2", "L3", "R1", "R2", "R3", ""],
+            text(&reading_order(&page))
+        );
+    }
+
+    #[test]
+    fn lines_crossing_the_gutter() {
+        let mut page = BookPage::new();
+        page.lines = vec![
+            vec![word(700, 0, 1300, 50, "HEAD")],
+            vec![word(100, 100, 900, 150, "L1")],
+            vec![word(1100, 100, 1900, 150, "R1")],
+            vec![word(100, 200, 900, 250, "L2")],
+            vec![word(1100, 200, 1900, 250, "R2")],
+            vec![word(700, 300, 1300, 350, "TITLE")],
+            vec![word(100, 400, 900, 450, "L3")],
+            vec![word(1100, 400, 1900, 450, "R3")],
+            vec![word(100, 500, 850, 550, "L4")],
+            vec![word(1100, 500, 1800, 550, "R4")],
+        ];
+        assert_eq!(
+            vec!["HEAD", "L1", "L2", "R1", "R2", "TITLE", "L3", "L4", "R3", "R4"],
+            text(&reading_order(&page))
+        );
+        let boxes: Vec<BBox> = page.lines.iter().filter_map(|l| line_bbox(l)).collect();
+        assert_eq!(vec![(100, 900), (1100, 1900)], detect_columns(&boxes));
+    }
+
+    #[test]
+    fn rotated_pages_keep_xml_order() {
+        let mut page = BookPage::new();
+        // Turned clockwise: each line runs down the page, and lines go right to left.
+        page.lines = vec![
+            vec![
+                word(900, 100, 950, 400, "first"),
+                word(900, 450, 950, 700, "line"),
+            ],
+            vec![
+                word(800, 100, 850, 400, "second"),
+                word(800, 450, 850, 700, "line"),
+            ],
+        ];
+        assert!(is_rotated(&page));
+        assert_eq!(page.lines, reading_order(&page).lines);
+
+        page.lines = vec![vec![
+            word(100, 0, 900, 50, "upright"),
+            word(950, 0, 1200, 50, "line"),
+        ]];
+        assert!(!is_rotated(&page));
+    }
+
+    #[test]
+    fn empty_lines_stay_in_place() {
+        let mut page = BookPage::new();
+        page.lines = vec![
+            vec![],
+            vec![word(100, 0, 900, 50, "one")],
+            vec![],
+            vec![],
+            vec![word(100, 200, 900, 250, "three")],
+            vec![word(100, 100, 900, 150, "two")],
+            vec![],
+        ];
+        assert_eq!(
+            vec!["", "one", "", "", "two", "", "three"],
+            text(&reading_order(&page))
+        );
+        page.lines.swap(4, 5);
+        assert_eq!(page.lines, reading_order(&page).lines);
+    }
+
+    #[test]
+    fn single_column_is_sorted_by_position() {
+        let mut page = BookPage::new();
+        page.lines = vec![
+            vec![word(100, 200, 900, 250, "second")],
+            vec![word(100, 100, 900, 150, "first")],
+            vec![word(1800, 0, 1900, 50, "47")],
+        ];
+        assert_eq!(vec!["47", "first", "second"], text(&reading_order(&page)));
+        assert!(detect_columns(&[]).is_empty());
+    }
+}
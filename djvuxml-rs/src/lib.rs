@@ -8,20 +8,42 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate unicode_normalization;
+extern crate unicode_segmentation;
 
-mod coords;
+pub mod alto;
+pub mod cache;
+pub mod context;
+pub mod coords;
+pub mod dump;
+pub mod export;
+pub mod format;
+pub mod geometry;
+pub mod hocr;
+pub mod lang;
+pub mod layout;
+pub mod meta;
+pub mod numeric;
+pub mod pagenum;
+pub mod poems;
+pub mod quality;
+pub mod running;
 mod params;
+pub mod plaintext;
 pub mod text;
+pub mod toc;
 pub mod types;
+pub mod writer;
 
+use coords::CoordsRepair;
 use quick_xml::events::*;
 use quick_xml::reader::Reader;
 use std::io::BufRead;
+use types::Book;
+use types::BookWord;
 use types::FastDjVu;
 use types::RichDjVu;
 use types::WordCoords;
-use types::Book;
-use types::BookWord;
 
 const WORD: &[u8] = b"WORD";
 const LINE: &[u8] = b"LINE";
@@ -32,10 +54,19 @@ const COORDS_ATTR: &[u8] = b"coords";
 /// Parse rich events from a DJVU XML document.
 ///
 /// Call ``callback`` whenever we encounter a ``RichDjVu`` element in a DJVU XML file.
-pub fn parse_rich_xml<R, F>(rdr: R, mut callback: F)
+pub fn parse_rich_xml<R, F>(rdr: R, callback: F)
+where
+    R: BufRead,
+    F: FnMut(RichDjVu),
+{
+    parse_rich_xml_with(rdr, &CoordsRepair::default(), callback)
+}
+
+/// Parse rich events from a DJVU XML document, repairing ``coords`` according to ``repair``.
+pub fn parse_rich_xml_with<R, F>(rdr: R, repair: &CoordsRepair, mut callback: F)
 where
     R: BufRead,
-    F: FnMut(RichDjVu) -> (),
+    F: FnMut(RichDjVu),
 {
     let mut xml = Reader::from_reader(rdr);
     xml.check_end_names(false);
@@ -49,22 +80,40 @@ where
             Ok(Event::Start(ref e)) => match e.name() {
                 WORD => {
                     in_word = true;
-                    recent_coords = e
+                    recent_coords = None;
+                    let attr = e
                         .attributes()
-                        .flat_map(|r| r)
-                        .find(|attr| attr.key == COORDS_ATTR)
-                        .and_then(|attr| WordCoords::parse_opt(&attr.value))
+                        .flatten()
+                        .find(|attr| attr.key == COORDS_ATTR);
+                    if let Some(attr) = attr {
+                        if let Some((coords, repaired)) =
+                            WordCoords::parse_repair(&attr.value, repair)
+                        {
+                            if repaired {
+                                callback(RichDjVu::RepairedCoords(
+                                    String::from_utf8_lossy(&attr.value).into_owned(),
+                                ));
+                            }
+                            recent_coords = Some(coords);
+                        }
+                    }
                 }
                 PARAM => match params::process(e) {
                     Err(e) => callback(RichDjVu::Error(format!("{:?}", e))),
                     Ok(Some(evt)) => callback(evt),
                     _ => {}
                 },
-                PAGE => match params::process_page(e) {
-                    Err(e) => callback(RichDjVu::Error(format!("{:?}", e))),
-                    Ok(Some(evt)) => callback(evt),
-                    _ => {}
-                },
+                PAGE => {
+                    callback(RichDjVu::StartPage);
+                    match params::process_page(e) {
+                        Err(e) => callback(RichDjVu::Error(format!("{:?}", e))),
+                        Ok(Some(evt)) => callback(evt),
+                        _ => {}
+                    }
+                    for attribute in params::page_attributes(e) {
+                        callback(attribute);
+                    }
+                }
                 LINE => callback(RichDjVu::StartLine),
                 _ => {} //println!("{:?}", std::str::from_utf8(e.name()))
             },
@@ -87,38 +136,93 @@ where
     }
 }
 
-pub fn process_book<R: BufRead>(
-    reader: R
-) -> Result<Book, String> {
+/// Collect a whole DJVU XML document into a ``Book``.
+///
+/// Every ``OBJECT`` becomes a page, even if it is blank or missing dimensions, so that
+/// ``book.pages[i]`` is always the i-th scan; check ``BookPage::status`` before trusting it.
+pub fn process_book<R: BufRead>(reader: R) -> Result<Book, String> {
+    process_book_with(reader, &CoordsRepair::default())
+}
+
+/// Collect a whole DJVU XML document into a ``Book``, repairing ``coords`` according to ``repair``.
+///
+/// How many coordinates were repaired, inferred or dropped is kept in ``book.coords``.
+pub fn process_book_with<R: BufRead>(reader: R, repair: &CoordsRepair) -> Result<Book, String> {
+    book_from_events(|callback| parse_rich_xml_with(reader, repair, callback), repair)
+}
+
+/// Collect the ``RichDjVu`` events of any parser into a ``Book``.
+///
+/// ``parse`` is given the callback to feed events to; ``repair`` decides whether words
+/// without coordinates are placed from their neighbors or dropped.
+pub fn book_from_events<P>(parse: P, repair: &CoordsRepair) -> Result<Book, String>
+where
+    P: FnOnce(&mut dyn FnMut(RichDjVu)),
+{
     let mut book = Book::new();
     let mut errors = Vec::new();
-    parse_rich_xml(reader, |item| match item {
+    // Words of the current line; we need the whole line before we can infer missing coords.
+    let mut pending: Vec<(Option<WordCoords>, String)> = Vec::new();
+    parse(&mut |item| match item {
+        RichDjVu::StartPage => {
+            finish_line(&mut book, &mut pending, repair);
+            book.start_page()
+        }
         RichDjVu::PageDimensions(w, h) => {
-            let mut page = book.current_page();
+            let page = book.current_page();
             page.width = w;
             page.height = h;
         }
         RichDjVu::PageDPI(dpi) => book.current_page().dpi = dpi,
-        RichDjVu::StartLine => book.current_page().lines.push(Vec::new()),
-        RichDjVu::Word(owc, ref text) => {
-            // Ditch words where location could not be parsed!
-            if let Some(coords) = owc {
-                book.current_line()
-                .push(BookWord::new(coords, text.clone()))
-            }
-        },
-        RichDjVu::EndLine => {}
-        RichDjVu::EndPage => book.end_page(),
+        RichDjVu::PageParam(name, value) => book.current_page().params.push((name, value)),
+        RichDjVu::PageAttribute(name, value) => {
+            book.current_page().attributes.push((name, value))
+        }
+        RichDjVu::StartLine => {
+            finish_line(&mut book, &mut pending, repair);
+            book.current_page().lines.push(Vec::new())
+        }
+        RichDjVu::RepairedCoords(_) => book.coords.repaired += 1,
+        RichDjVu::Word(owc, text) => pending.push((owc, text)),
+        RichDjVu::EndLine | RichDjVu::EndPage => finish_line(&mut book, &mut pending, repair),
         RichDjVu::Error(msg) => errors.push(msg),
     });
+    finish_line(&mut book, &mut pending, repair);
 
-    // Remove last page if invalid:
-    if !book.current_page().valid() {
-        let _ = book.pages.pop();
-    }
     Ok(book)
 }
 
+/// Move the pending words onto the current line, placing or dropping any without coordinates.
+fn finish_line(
+    book: &mut Book,
+    pending: &mut Vec<(Option<WordCoords>, String)>,
+    repair: &CoordsRepair,
+) {
+    if pending.is_empty() {
+        return;
+    }
+    let placed = if repair.infer_from_line {
+        coords::infer_line(pending)
+    } else {
+        pending.iter().map(|w| w.0).collect()
+    };
+    for ((original, text), found) in pending.drain(..).zip(placed) {
+        match found {
+            Some(coords) => {
+                if original.is_none() {
+                    book.coords.inferred += 1;
+                }
+                book.current_line().push(BookWord::new(coords, text))
+            }
+            // Words without a location cannot be placed; count them against the page.
+            None => {
+                book.coords.dropped += 1;
+                book.current_page().bad_coords += 1
+            }
+        }
+    }
+}
+
 /// Parse simple events from a DJVU XML document.
 ///
 /// Call ``callback`` whenever we encounter a ``FastDjVu`` element in a DJVU XML file.
@@ -159,6 +263,34 @@ where
     }
 }
 
+/// Anything that can replay a book as ``FastDjVu`` events: DJVU XML, or a parsed ``Book``.
+pub trait FastEvents {
+    fn fast_events<F: FnMut(FastDjVu)>(self, callback: F);
+}
+
+impl<R: BufRead> FastEvents for R {
+    fn fast_events<F: FnMut(FastDjVu)>(self, callback: F) {
+        parse_fast_xml(self, callback)
+    }
+}
+
+impl FastEvents for &Book {
+    /// Words dropped for bad coordinates while parsing are not replayed.
+    fn fast_events<F: FnMut(FastDjVu)>(self, mut callback: F) {
+        for page in &self.pages {
+            callback(FastDjVu::StartPage);
+            for line in &page.lines {
+                callback(FastDjVu::StartLine);
+                for word in line {
+                    callback(FastDjVu::Word(word.text.clone()));
+                }
+                callback(FastDjVu::EndLine);
+            }
+            callback(FastDjVu::EndPage);
+        }
+    }
+}
+
 /// `trim_book` returns the internet archive id from a path in a zip file.
 ///
 /// ```
@@ -167,14 +299,61 @@ where
 /// assert_eq!("abelincjohn02morsrich", trim_book("inex500/abelincjohn02morsrich.xml"));
 /// assert_eq!("abelincjohn02morsrich", trim_book("abelincjohn02morsrich_djvu.xml"));
 /// assert_eq!("abelincjohn02morsrich", trim_book("abelincjohn02morsrich.xml"));
+/// assert_eq!("abelincjohn02morsrich", trim_book("abelincjohn02morsrich_hocr.html"));
+/// assert_eq!("abelincjohn02morsrich", trim_book("abelincjohn02morsrich_alto.xml"));
+/// assert_eq!("pg1065", trim_book("pg1065.txt"));
 /// ```
 pub fn trim_book(input: &str) -> &str {
-    let stripped = input
-        .trim_right_matches("_djvu.xml")
-        .trim_right_matches(".xml");
+    let stripped = ["_djvu.xml", "_hocr.html", "_alto.xml", ".xml", ".html", ".txt"]
+        .iter()
+        .find(|suffix| input.ends_with(*suffix))
+        .map_or(input, |suffix| &input[..input.len() - suffix.len()]);
     if let Some(pt) = stripped.rfind('/') {
         &stripped[(pt + 1)..]
     } else {
         stripped
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::PageStatus;
+
+    const THREE_PAGES: &str = r#"<DjVuXML><BODY>
+<OBJECT height="3000" width="2000"><PARAM name="DPI" value="600"/>
+<HIDDENTEXT><PAGECOLUMN><REGION><PARAGRAPH>
+<LINE><WORD coords="814,1248,1012,1168,1247">THE</WORD><WORD coords="junk">TRAGEDY</WORD></LINE>
+</PARAGRAPH></REGION></PAGECOLUMN></HIDDENTEXT></OBJECT>
+<OBJECT height="3000" width="2000"><PARAM name="DPI" value="600"/><HIDDENTEXT/></OBJECT>
+<OBJECT height="0" width="2000">
+<HIDDENTEXT><LINE><WORD coords="814,1248,1012,1168,1247">HAMLET</WORD></LINE></HIDDENTEXT></OBJECT>
+</BODY></DjVuXML>"#;
+
+    #[test]
+    fn keeps_every_page() {
+        let book = process_book_with(THREE_PAGES.as_bytes(), &CoordsRepair::none()).unwrap();
+        assert_eq!(3, book.pages.len());
+        assert_eq!(PageStatus::BadCoords(1), book.pages[0].status());
+        assert_eq!(PageStatus::Blank, book.pages[1].status());
+        assert_eq!(PageStatus::MissingDims, book.pages[2].status());
+        assert_eq!("HAMLET\n", book.get_page_text(2));
+        assert_eq!(1, book.coords.dropped);
+    }
+
+    #[test]
+    fn infers_damaged_coords() {
+        let book = process_book(
+            r#"<OBJECT height="3000" width="2000"><PARAM name="DPI" value="600"/>
+<LINE><WORD coords="814,1248,1012,1168,1247">THE</WORD><WORD coords="1,2,3">OF</WORD>
+<WORD coords="1068,1250,1548,1168,l248">TRAGEDY</WORD></LINE></OBJECT>"#
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(PageStatus::Ok, book.pages[0].status());
+        assert_eq!("THE\tOF\tTRAGEDY\n", book.get_page_text(0));
+        assert_eq!(1, book.coords.repaired);
+        assert_eq!(1, book.coords.inferred);
+        assert_eq!(0, book.coords.dropped);
+    }
+}
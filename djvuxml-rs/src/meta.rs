@@ -0,0 +1,223 @@
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::BufRead;
+
+lazy_static! {
+    static ref YEAR: Regex = Regex::new(r"\b(1\d|20)\d\d\b").unwrap();
+}
+
+/// What an Internet Archive item's ``_meta.xml`` says about its book.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BookMetadata {
+    pub identifier: Option<String>,
+    pub title: Option<String>,
+    pub creators: Vec<String>,
+    /// The date as catalogued, which may be ``1892``, ``1892-05-01`` or ``[ca. 1890]``.
+    pub date: Option<String>,
+    /// The first year in ``date``, for filtering.
+    pub year: Option<u32>,
+    /// Languages as catalogued, usually MARC codes like ``eng`` but sometimes names.
+    pub languages: Vec<String>,
+}
+
+/// Read the fields we keep from a ``_meta.xml``; repeated fields other than ``creator`` and
+/// ``language`` keep their first value.
+pub fn parse_meta<R: BufRead>(reader: R) -> Result<BookMetadata, String> {
+    let mut xml = Reader::from_reader(reader);
+    xml.check_end_names(false);
+    let mut buf = Vec::new();
+    let mut meta = BookMetadata::default();
+    let mut depth = 0;
+    let mut field: Option<Vec<u8>> = None;
+    loop {
+        match xml.read_event(&mut buf) {
+            Ok(Event::Eof) => break,
+            Ok(Event::Start(ref e)) => {
+                depth += 1;
+                // Fields are the children of ``<metadata>``.
+                field = if depth == 2 {
+                    Some(e.name().to_vec())
+                } else {
+                    None
+                };
+            }
+            Ok(Event::End(_)) => {
+                depth -= 1;
+                field = None;
+            }
+            Ok(Event::Text(ref e)) | Ok(Event::CData(ref e)) => {
+                let name = match field {
+                    Some(ref name) => name,
+                    None => continue,
+                };
+                let text = e
+                    .unescape_and_decode(&xml)
+                    .map_err(|e| format!("{:?}", e))?;
+                let text = text.trim();
+                if text.is_empty() {
+                    continue;
+                }
+                match &name[..] {
+                    b"identifier" => set_once(&mut meta.identifier, text),
+                    b"title" => set_once(&mut meta.title, text),
+                    b"creator" => meta.creators.push(text.to_owned()),
+                    b"date" => set_once(&mut meta.date, text),
+                    b"language" => meta.languages.push(text.to_owned()),
+                    _ => {}
+                }
+            }
+            Err(e) => return Err(format!("{:?}", e)),
+            _ => {}
+        }
+        buf.clear();
+    }
+    meta.year = meta
+        .date
+        .as_ref()
+        .and_then(|d| YEAR.find(d))
+        .and_then(|m| m.as_str().parse().ok());
+    Ok(meta)
+}
+
+fn set_once(slot: &mut Option<String>, text: &str) {
+    if slot.is_none() {
+        *slot = Some(text.to_owned());
+    }
+}
+
+/// What a file of an Internet Archive item is, by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemFile<'a> {
+    /// A book in a format we read, under its item's path, e.g. ``dir/abc`` for
+    /// ``dir/abc_djvu.xml``.
+    Book(&'a str),
+    /// The ``_meta.xml`` of the item at this path.
+    Meta(&'a str),
+    /// Anything else: directories, page images, PDFs, torrents, and the item's other XML and
+    /// text, such as MARC records, file lists and the ``_djvu.txt`` that repeats the
+    /// ``_djvu.xml``.
+    Other,
+}
+
+/// Item files that would otherwise pass for books.
+const OTHER_SUFFIXES: &[&str] = &[
+    "_files.xml",
+    "_marc.xml",
+    "_dc.xml",
+    "_reviews.xml",
+    "_scandata.xml",
+    "_djvu.txt",
+];
+/// Names of books, the format we would rather read first.
+const BOOK_SUFFIXES: &[&str] = &[
+    "_djvu.xml",
+    "_hocr.html",
+    "_alto.xml",
+    ".xml",
+    ".html",
+    ".txt",
+];
+
+/// Classify a file of an item, so that books can be paired with their ``_meta.xml``: the two
+/// are paired when they have the same path.
+pub fn item_file(name: &str) -> ItemFile<'_> {
+    if OTHER_SUFFIXES.iter().any(|s| name.ends_with(s)) {
+        ItemFile::Other
+    } else if let Some(path) = name.strip_suffix("_meta.xml") {
+        ItemFile::Meta(path)
+    } else {
+        BOOK_SUFFIXES
+            .iter()
+            .find_map(|s| name.strip_suffix(s))
+            .map_or(ItemFile::Other, ItemFile::Book)
+    }
+}
+
+/// Indices of the files to read as books, in order: one per item, in the first format of
+/// ``BOOK_SUFFIXES`` it has, so an item with both ``_djvu.xml`` and ``_hocr.html`` is read once.
+pub fn pick_books<S: AsRef<str>>(names: &[S]) -> Vec<usize> {
+    let rank = |name: &str| BOOK_SUFFIXES.iter().position(|s| name.ends_with(s));
+    let mut best: HashMap<&str, usize> = HashMap::new();
+    for (i, name) in names.iter().enumerate() {
+        if let ItemFile::Book(item) = item_file(name.as_ref()) {
+            let better = match best.get(item) {
+                Some(&j) => rank(name.as_ref()) < rank(names[j].as_ref()),
+                None => true,
+            };
+            if better {
+                best.insert(item, i);
+            }
+        }
+    }
+    let mut picked: Vec<usize> = best.values().cloned().collect();
+    picked.sort();
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const META: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<metadata>
+  <identifier>poemsofkeats00keat</identifier>
+  <mediatype>texts</mediatype>
+  <title>The poems of John Keats &amp; others</title>
+  <creator>Keats, John, 1795-1821</creator>
+  <creator>Colvin, Sidney</creator>
+  <date>[ca. 1905]</date>
+  <language>eng</language>
+  <collection><![CDATA[americana]]></collection>
+</metadata>"#;
+
+    #[test]
+    fn reads_meta() {
+        let meta = parse_meta(META.as_bytes()).unwrap();
+        assert_eq!(Some("poemsofkeats00keat"), meta.identifier.as_deref());
+        assert_eq!(
+            Some("The poems of John Keats & others"),
+            meta.title.as_deref()
+        );
+        assert_eq!(
+            vec!["Keats, John, 1795-1821", "Colvin, Sidney"],
+            meta.creators
+        );
+        assert_eq!(Some("[ca. 1905]"), meta.date.as_deref());
+        assert_eq!(Some(1905), meta.year);
+        assert_eq!(vec!["eng"], meta.languages);
+        assert_eq!(
+            BookMetadata::default(),
+            parse_meta(&b"<metadata/>"[..]).unwrap()
+        );
+    }
+
+    #[test]
+    fn pairs_item_files() {
+        assert_eq!(ItemFile::Book("a/abc"), item_file("a/abc_djvu.xml"));
+        assert_eq!(ItemFile::Meta("a/abc"), item_file("a/abc_meta.xml"));
+        assert_eq!(ItemFile::Book("pg1065"), item_file("pg1065.txt"));
+        assert_eq!(ItemFile::Other, item_file("a/abc_djvu.txt"));
+        assert_eq!(ItemFile::Other, item_file("a/abc_files.xml"));
+        assert_eq!(ItemFile::Other, item_file("a/"));
+        assert_eq!(ItemFile::Other, item_file("a/abc_marc.xml"));
+        assert_eq!(ItemFile::Other, item_file("a/abc.pdf"));
+        assert_eq!(ItemFile::Other, item_file("a/abc_chocr.html.gz"));
+        assert_eq!(ItemFile::Book("a/abc"), item_file("a/abc_hocr.html"));
+    }
+
+    #[test]
+    fn one_book_per_item() {
+        let names = [
+            "a/abc_hocr.html",
+            "a/abc_djvu.xml",
+            "a/abc_djvu.txt",
+            "a/abc_meta.xml",
+            "a/abc_marc.xml",
+            "a/abc.pdf",
+            "b/def_hocr.html",
+        ];
+        assert_eq!(vec![1, 6], pick_books(&names));
+    }
+}
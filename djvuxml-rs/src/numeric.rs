@@ -0,0 +1,185 @@
+use context::context_windows;
+use regex::Regex;
+use std::io::{self, Write};
+use FastEvents;
+
+/// How many events to keep on either side of a number, unless told otherwise.
+pub const WINDOW_SIZE: usize = 32;
+
+lazy_static! {
+    static ref DIGITS: Regex = Regex::new(r"^\d+$").unwrap();
+    static ref ROMAN: Regex =
+        Regex::new(r"^M{0,4}(CM|CD|D?C{0,3})(XC|XL|L?X{0,3})(IX|IV|V?I{0,3})$").unwrap();
+    static ref YEAR: Regex = Regex::new(r"^(1[4-9]|20)\d\d$").unwrap();
+}
+
+/// Which words count as numbers.
+#[derive(Debug, Clone, Default)]
+pub enum NumberPattern {
+    /// Only ASCII or Unicode digits, e.g. page numbers.
+    #[default]
+    Digits,
+    /// Roman numerals in one case, like ``XIV`` or ``xiv``; note that ``I`` is one.
+    Roman,
+    /// Four-digit years from 1400 to 2099.
+    Years,
+    /// Anything the expression matches.
+    Regex(Regex),
+}
+
+impl NumberPattern {
+    /// ``digits``, ``roman``, ``years``, or ``regex:`` followed by an expression.
+    pub fn parse(spec: &str) -> Result<NumberPattern, String> {
+        match spec {
+            "digits" => Ok(NumberPattern::Digits),
+            "roman" => Ok(NumberPattern::Roman),
+            "years" => Ok(NumberPattern::Years),
+            _ if spec.starts_with("regex:") => Regex::new(&spec["regex:".len()..])
+                .map(NumberPattern::Regex)
+                .map_err(|e| format!("Bad number pattern {:?}: {}", spec, e)),
+            _ => Err(format!(
+                "Unknown number pattern {:?}; expected digits, roman, years or regex:EXPR.",
+                spec
+            )),
+        }
+    }
+
+    /// Does this word, ignoring punctuation around it, count as a number?
+    pub fn matches(&self, word: &str) -> bool {
+        let word = trim_punctuation(word);
+        if word.is_empty() {
+            return false;
+        }
+        match *self {
+            NumberPattern::Digits => DIGITS.is_match(word),
+            NumberPattern::Roman => {
+                (word == word.to_uppercase() || word == word.to_lowercase())
+                    && ROMAN.is_match(&word.to_uppercase())
+            }
+            NumberPattern::Years => YEAR.is_match(word),
+            NumberPattern::Regex(ref re) => re.is_match(word),
+        }
+    }
+}
+
+/// What to look for and how much to keep around it.
+#[derive(Debug, Clone)]
+pub struct NumericOptions {
+    /// How many events to keep on either side of a number.
+    pub window: usize,
+    pub pattern: NumberPattern,
+}
+
+impl Default for NumericOptions {
+    fn default() -> NumericOptions {
+        NumericOptions {
+            window: WINDOW_SIZE,
+            pattern: NumberPattern::default(),
+        }
+    }
+}
+
+/// A number found in a book, with what surrounds it.
+///
+/// Context holds words and the page and line boundaries, written as ``<P>``, ``<L>``,
+/// ``</L>`` and ``</P>``; it is shorter than the window at the start and end of a book.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NumericWindow {
+    pub book: String,
+    pub page: u32,
+    /// Index of the number among the words of its page.
+    pub word: u32,
+    /// The number as it appears in the book, without punctuation around it.
+    pub number: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+fn trim_punctuation(word: &str) -> &str {
+    word.trim().trim_matches(|c: char| !c.is_alphanumeric())
+}
+
+/// Write every number in a book with ``options.window`` events of context on either side,
+/// as one JSON ``NumericWindow`` per line.
+pub fn numeric_windows<S: FastEvents, W: Write>(
+    book: &str,
+    source: S,
+    options: &NumericOptions,
+    out: &mut W,
+) -> io::Result<()> {
+    context_windows(
+        book,
+        source,
+        options.window,
+        |w| options.pattern.matches(w),
+        |window| {
+            let record = NumericWindow {
+                number: trim_punctuation(&window.keyword).to_owned(),
+                book: window.book,
+                page: window.page,
+                word: window.word,
+                before: window.before,
+                after: window.after,
+            };
+            serde_json::to_writer(&mut *out, &record)?;
+            writeln!(out)
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<OBJECT height="3000" width="2000"><PARAM name="DPI" value="600"/>
+<LINE><WORD coords="1,2,3,4,5">Page</WORD><WORD coords="1,2,3,4,5">47.</WORD></LINE>
+<LINE><WORD coords="1,2,3,4,5">Canto</WORD><WORD coords="1,2,3,4,5">XIV,</WORD><WORD coords="1,2,3,4,5">1819</WORD></LINE>
+</OBJECT>"#;
+
+    fn windows(options: &NumericOptions) -> Vec<NumericWindow> {
+        let mut out = Vec::new();
+        numeric_windows("book", XML.as_bytes(), options, &mut out).unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn finds_numbers() {
+        let found = windows(&NumericOptions::default());
+        assert_eq!(2, found.len());
+        assert_eq!("47", found[0].number);
+        assert_eq!((0, 1), (found[0].page, found[0].word));
+        assert_eq!(vec!["<P>", "<L>", "Page"], found[0].before);
+        assert_eq!("</P>", found[0].after.last().unwrap());
+        assert_eq!("1819", found[1].number);
+        assert_eq!(4, found[1].word);
+    }
+
+    #[test]
+    fn window_size() {
+        let found = windows(&NumericOptions {
+            window: 2,
+            pattern: NumberPattern::Years,
+        });
+        assert_eq!(1, found.len());
+        assert_eq!(vec!["Canto", "XIV,"], found[0].before);
+        assert_eq!(vec!["</L>", "</P>"], found[0].after);
+    }
+
+    #[test]
+    fn patterns() {
+        let roman = NumberPattern::parse("roman").unwrap();
+        assert!(roman.matches("XIV,"));
+        assert!(roman.matches("xlii"));
+        assert!(!roman.matches("XiV"));
+        assert!(!roman.matches("IIII"));
+        let custom = NumberPattern::parse(r"regex:^\d+(st|nd|rd|th)$").unwrap();
+        assert!(custom.matches("22nd"));
+        assert!(NumberPattern::parse("regex:(").is_err());
+        assert!(NumberPattern::parse("hex").is_err());
+        assert!(!NumberPattern::Years.matches("1066"));
+    }
+}
@@ -0,0 +1,224 @@
+use running::edge_lines;
+use std;
+use std::collections::HashMap;
+use types::{Book, BookPage};
+
+/// Look this many scans either side of a page for page numbers that agree with each other.
+const WINDOW: usize = 10;
+/// A scan-to-printed offset needs this many detected numbers before we believe it.
+const MIN_SUPPORT: usize = 2;
+/// Printed page numbers longer than this are more likely years or other numbers.
+const MAX_DIGITS: usize = 4;
+
+/// A printed page number: front matter is usually numbered in roman numerals.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PageLabel {
+    Arabic(u32),
+    Roman(u32),
+}
+
+impl std::fmt::Display for PageLabel {
+    fn fmt(&self, output: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match *self {
+            PageLabel::Arabic(n) => write!(output, "{}", n),
+            PageLabel::Roman(n) => write!(output, "{}", to_roman(n)),
+        }
+    }
+}
+
+const ROMAN: &[(u32, &str)] = &[
+    (1000, "m"),
+    (900, "cm"),
+    (500, "d"),
+    (400, "cd"),
+    (100, "c"),
+    (90, "xc"),
+    (50, "l"),
+    (40, "xl"),
+    (10, "x"),
+    (9, "ix"),
+    (5, "v"),
+    (4, "iv"),
+    (1, "i"),
+];
+
+/// Write ``n`` as a lowercase roman numeral.
+pub fn to_roman(mut n: u32) -> String {
+    let mut out = String::new();
+    for &(value, digits) in ROMAN {
+        while n >= value {
+            out.push_str(digits);
+            n -= value;
+        }
+    }
+    out
+}
+
+/// Read a well-formed roman numeral, in either case.
+///
+/// Only the canonical spelling of each number is accepted, so ordinary words made of the
+/// same letters ("mild", "dim", "civil") are rejected.
+pub fn parse_roman(input: &str) -> Option<u32> {
+    let lower = input.to_lowercase();
+    if lower.is_empty() || !lower.chars().all(|c| "ivxlcdm".contains(c)) {
+        return None;
+    }
+    let mut rest = lower.as_str();
+    let mut total = 0;
+    for &(value, digits) in ROMAN {
+        while rest.starts_with(digits) {
+            total += value;
+            rest = &rest[digits.len()..];
+        }
+    }
+    if rest.is_empty() && to_roman(total) == lower {
+        Some(total)
+    } else {
+        None
+    }
+}
+
+/// Read a token as a page number, ignoring brackets and punctuation around it.
+pub fn parse_label(token: &str) -> Option<PageLabel> {
+    let token = token.trim_matches(|c: char| !c.is_alphanumeric());
+    if token.is_empty() {
+        return None;
+    }
+    if token.chars().all(|c| c.is_ascii_digit()) {
+        if token.len() > MAX_DIGITS {
+            return None;
+        }
+        return token
+            .parse::<u32>()
+            .ok()
+            .filter(|&n| n > 0)
+            .map(PageLabel::Arabic);
+    }
+    parse_roman(token).map(PageLabel::Roman)
+}
+
+/// Every token in the header or footer lines of a page that looks like a page number.
+pub fn page_number_candidates(page: &BookPage) -> Vec<PageLabel> {
+    edge_lines(page, true)
+        .into_iter()
+        .chain(edge_lines(page, false))
+        .flat_map(|i| page.lines[i].iter())
+        .filter_map(|w| parse_label(&w.text))
+        .collect()
+}
+
+/// Fit printed numbers to scan indices from noisy observations of ``(scan, printed)``.
+///
+/// Within a run of numbered pages, ``printed - scan`` is constant; it changes where unnumbered
+/// plates are inserted or numbering restarts. Each page takes the offset seen most often
+/// among nearby observations, and only pages between two observations of that offset are
+/// labeled, so we interpolate but never extrapolate. Labels that would break the increasing
+/// order of the book are dropped.
+pub fn fit_offsets(observations: &[(usize, u32)], num_pages: usize) -> Vec<Option<u32>> {
+    let mut output = vec![None; num_pages];
+    let mut last: Option<u32> = None;
+    for (i, slot) in output.iter_mut().enumerate() {
+        let lo = i.saturating_sub(WINDOW);
+        let hi = i + WINDOW;
+        let mut counts: HashMap<i64, usize> = HashMap::new();
+        for &(scan, printed) in observations {
+            if scan >= lo && scan <= hi {
+                *counts.entry(i64::from(printed) - scan as i64).or_insert(0) += 1;
+            }
+        }
+        let best = counts
+            .into_iter()
+            .filter(|&(_, n)| n >= MIN_SUPPORT)
+            .max_by_key(|&(offset, n)| (n, -offset));
+        let offset = match best {
+            Some((offset, _)) => offset,
+            None => continue,
+        };
+        let agrees = |&&(scan, printed): &&(usize, u32)| {
+            i64::from(printed) - scan as i64 == offset && scan >= lo && scan <= hi
+        };
+        let before = observations
+            .iter()
+            .filter(agrees)
+            .any(|&(scan, _)| scan <= i);
+        let after = observations
+            .iter()
+            .filter(agrees)
+            .any(|&(scan, _)| scan >= i);
+        let value = i as i64 + offset;
+        if before && after && value > 0 && value <= i64::from(u32::MAX) {
+            let value = value as u32;
+            if last.map(|l| value > l).unwrap_or(true) {
+                *slot = Some(value);
+                last = Some(value);
+            }
+        }
+    }
+    output
+}
+
+/// Find the printed page number of every page in the book and store it in ``page.label``.
+///
+/// Arabic numbering takes precedence; roman numerals only label pages the arabic run does
+/// not cover (usually the front matter).
+pub fn label_pages(book: &mut Book) {
+    let mut arabic = Vec::new();
+    let mut roman = Vec::new();
+    for (i, page) in book.pages.iter().enumerate() {
+        for label in page_number_candidates(page) {
+            match label {
+                PageLabel::Arabic(n) => arabic.push((i, n)),
+                PageLabel::Roman(n) => roman.push((i, n)),
+            }
+        }
+    }
+    let n = book.pages.len();
+    let arabic = fit_offsets(&arabic, n);
+    let roman = fit_offsets(&roman, n);
+    for (i, page) in book.pages.iter_mut().enumerate() {
+        page.label = arabic[i]
+            .map(PageLabel::Arabic)
+            .or_else(|| roman[i].map(PageLabel::Roman));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roman_numerals() {
+        assert_eq!(Some(4), parse_roman("iv"));
+        assert_eq!(Some(47), parse_roman("XLVII"));
+        assert_eq!(Some(1889), parse_roman("mdccclxxxix"));
+        assert_eq!(None, parse_roman("iiii"));
+        assert_eq!(None, parse_roman("civil"));
+        assert_eq!(None, parse_roman("dim"));
+        assert_eq!("xlvii", to_roman(47));
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(Some(PageLabel::Arabic(47)), parse_label("[47]"));
+        assert_eq!(Some(PageLabel::Roman(9)), parse_label("ix."));
+        assert_eq!(None, parse_label("18891"));
+        assert_eq!(None, parse_label("POEMS."));
+        assert_eq!("p. 47", format!("p. {}", PageLabel::Arabic(47)));
+        assert_eq!("xii", PageLabel::Roman(12).to_string());
+    }
+
+    #[test]
+    fn fit_with_gaps_and_noise() {
+        // Scans 2..8 are pages 1..7, scan 4 has no number, scan 5 misread "4" as "1".
+        let observations = vec![(2, 1), (3, 2), (5, 1), (6, 5), (7, 6), (8, 7)];
+        let fit = fit_offsets(&observations, 11);
+        assert_eq!(None, fit[0]);
+        assert_eq!(None, fit[1]);
+        assert_eq!(Some(1), fit[2]);
+        assert_eq!(Some(3), fit[4]);
+        assert_eq!(Some(4), fit[5]);
+        assert_eq!(Some(7), fit[8]);
+        // No extrapolation past the last number we saw.
+        assert_eq!(None, fit[9]);
+    }
+}
@@ -51,11 +51,13 @@ pub(crate) fn process(e: &BytesStart) -> Result<Option<RichDjVu>, ParamError> {
     match name.as_str() {
         "DPI" => {
             let val = get_attribute(e, VALUE_KEY)?;
-            return Ok(Some(RichDjVu::PageDPI(val.parse::<u32>()?)));
+            Ok(Some(RichDjVu::PageDPI(val.parse::<u32>()?)))
+        }
+        _ => {
+            let val = get_attribute(e, VALUE_KEY).unwrap_or_default();
+            Ok(Some(RichDjVu::PageParam(name, val)))
         }
-        _ => {}
     }
-    Ok(None)
 }
 
 pub(crate) fn process_page(e: &BytesStart) -> Result<Option<RichDjVu>, ParamError> {
@@ -63,3 +65,17 @@ pub(crate) fn process_page(e: &BytesStart) -> Result<Option<RichDjVu>, ParamErro
     let h = get_attribute(e, HEIGHT_KEY)?.parse::<u32>()?;
     Ok(Some(RichDjVu::PageDimensions(w, h)))
 }
+
+/// Every attribute of an ``OBJECT`` but its dimensions; unreadable ones are skipped.
+pub(crate) fn page_attributes(e: &BytesStart) -> Vec<RichDjVu> {
+    e.attributes()
+        .flatten()
+        .filter(|attr| attr.key != WIDTH_KEY && attr.key != HEIGHT_KEY)
+        .filter_map(|attr| {
+            let key = std::str::from_utf8(attr.key).ok()?;
+            let value = attr.unescaped_value().ok()?;
+            let value = std::str::from_utf8(&value).ok()?;
+            Some(RichDjVu::PageAttribute(key.to_owned(), value.to_owned()))
+        })
+        .collect()
+}
@@ -0,0 +1,231 @@
+use coords::CoordsRepair;
+use regex::Regex;
+use std::cmp;
+use std::io::BufRead;
+use types::{Book, Geometry, RichDjVu, WordCoords};
+
+/// Synthetic pages are US letter at this resolution, with one-inch margins.
+pub const DPI: u32 = 300;
+const PAGE_WIDTH: u32 = 2550;
+const PAGE_HEIGHT: u32 = 3300;
+const MARGIN: u32 = 300;
+/// Ten characters to the inch and six lines to the inch, like a typewriter.
+const CHAR_WIDTH: u32 = 30;
+const LINE_HEIGHT: u32 = 50;
+/// Height of a word's box, and of its baseline, below the top of its line.
+const WORD_HEIGHT: u32 = 40;
+const BASELINE: u32 = 32;
+const TAB_WIDTH: usize = 8;
+
+/// As many lines as fit between the margins of a synthetic page.
+pub const LINES_PER_PAGE: usize = ((PAGE_HEIGHT - 2 * MARGIN) / LINE_HEIGHT) as usize;
+
+/// How plain text is cut into pages.
+#[derive(Debug, Clone)]
+pub struct PlainTextOptions {
+    /// Lines matching this start a new page and are dropped, e.g. ``^\[Pg \d+\]$``.
+    pub page_delimiter: Option<Regex>,
+    /// A page running longer than this, blank lines included, continues on a new one.
+    pub lines_per_page: usize,
+}
+
+impl Default for PlainTextOptions {
+    fn default() -> Self {
+        PlainTextOptions {
+            page_delimiter: None,
+            lines_per_page: LINES_PER_PAGE,
+        }
+    }
+}
+
+fn to_u16(x: u32) -> u16 {
+    cmp::min(x, u32::from(u16::MAX)) as u16
+}
+
+/// The words of a line with the column each starts at, tabs expanded.
+fn words_by_column(line: &str) -> Vec<(usize, String)> {
+    let mut words = Vec::new();
+    let mut column = 0;
+    let mut current = String::new();
+    let mut start = 0;
+    for c in line.chars() {
+        if c.is_whitespace() {
+            if !current.is_empty() {
+                words.push((start, current.clone()));
+                current.clear();
+            }
+            column = if c == '\t' {
+                (column / TAB_WIDTH + 1) * TAB_WIDTH
+            } else {
+                column + 1
+            };
+        } else {
+            if current.is_empty() {
+                start = column;
+            }
+            current.push(c);
+            column += 1;
+        }
+    }
+    if !current.is_empty() {
+        words.push((start, current));
+    }
+    words
+}
+
+/// Lay out one page of text, set in a monospace font from the top left margin.
+fn emit_page<F: FnMut(RichDjVu)>(lines: &[String], callback: &mut F) {
+    let lines: Vec<Vec<(usize, String)>> = lines.iter().map(|l| words_by_column(l)).collect();
+    let columns = lines
+        .iter()
+        .filter_map(|l| l.last())
+        .map(|&(start, ref word)| start + word.chars().count())
+        .max()
+        .unwrap_or(0) as u32;
+    let rows = lines.len() as u32;
+    callback(RichDjVu::StartPage);
+    callback(RichDjVu::PageDimensions(
+        cmp::max(PAGE_WIDTH, 2 * MARGIN + columns * CHAR_WIDTH),
+        cmp::max(PAGE_HEIGHT, 2 * MARGIN + rows * LINE_HEIGHT),
+    ));
+    callback(RichDjVu::PageDPI(DPI));
+    for (row, words) in lines.into_iter().enumerate() {
+        // Blank lines are left as space between lines, as OCR would see them.
+        if words.is_empty() {
+            continue;
+        }
+        let top = MARGIN + row as u32 * LINE_HEIGHT;
+        callback(RichDjVu::StartLine);
+        for (column, word) in words {
+            let left = MARGIN + column as u32 * CHAR_WIDTH;
+            let coords = WordCoords {
+                x1: to_u16(left),
+                y1: to_u16(top),
+                x2: to_u16(left + word.chars().count() as u32 * CHAR_WIDTH),
+                y2: to_u16(top + WORD_HEIGHT),
+                base: to_u16(top + BASELINE),
+            };
+            callback(RichDjVu::Word(Some(coords), word));
+        }
+        callback(RichDjVu::EndLine);
+    }
+    callback(RichDjVu::EndPage);
+}
+
+/// Parse plain text into the same events as ``parse_rich_xml``, with made-up coordinates.
+///
+/// Pages end at form feeds, at lines matching ``options.page_delimiter``, and when they are
+/// full. Each line of text is a line of the page, set in a monospace font, so leading spaces
+/// become indentation and blank lines become vertical space. A book parsed this way should be
+/// marked ``Geometry::Synthetic``, as ``process_text`` does.
+pub fn parse_text<R, F>(reader: R, options: &PlainTextOptions, mut callback: F)
+where
+    R: BufRead,
+    F: FnMut(RichDjVu),
+{
+    let mut page: Vec<String> = Vec::new();
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                callback(RichDjVu::Error(format!("{:?}", e)));
+                break;
+            }
+        };
+        let line = line.trim_end_matches('\r').trim_start_matches('\u{feff}');
+        if let Some(ref delimiter) = options.page_delimiter {
+            if delimiter.is_match(line) {
+                // A full page may have just ended; don't follow it with an empty one.
+                if !page.is_empty() {
+                    emit_page(&page, &mut callback);
+                    page.clear();
+                }
+                continue;
+            }
+        }
+        let mut parts = line.split('\x0c');
+        page.push(parts.next().unwrap_or_default().to_owned());
+        for part in parts {
+            // A leading or repeated form feed has no page to end.
+            if page.iter().any(|l| !l.trim().is_empty()) {
+                emit_page(&page, &mut callback);
+            }
+            page.clear();
+            page.push(part.to_owned());
+        }
+        if page.len() >= options.lines_per_page {
+            emit_page(&page, &mut callback);
+            page.clear();
+        }
+    }
+    if page.iter().any(|l| !l.trim().is_empty()) {
+        emit_page(&page, &mut callback);
+    }
+}
+
+/// Collect plain text into a ``Book`` with ``Geometry::Synthetic``.
+pub fn process_text<R: BufRead>(reader: R, options: &PlainTextOptions) -> Result<Book, String> {
+    let mut book = ::book_from_events(
+        |callback| parse_text(reader, options, callback),
+        &CoordsRepair::default(),
+    )?;
+    book.geometry = Geometry::Synthetic;
+    Ok(book)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str =
+        "TO AUTUMN\n\n  Season of mists and mellow fruitfulness,\n\tClose bosom-friend\n\x0cII.\n";
+
+    #[test]
+    fn lays_out_lines() {
+        let book = process_text(TEXT.as_bytes(), &PlainTextOptions::default()).unwrap();
+        assert_eq!(Geometry::Synthetic, book.geometry);
+        assert_eq!(2, book.pages.len());
+        let page = &book.pages[0];
+        assert_eq!((2550, 3300, 300), (page.width, page.height, page.dpi));
+        assert_eq!(3, page.lines.len());
+        assert_eq!(
+            "TO\tAUTUMN\nSeason\tof\tmists\tand\tmellow\tfruitfulness,\nClose\tbosom-friend\n",
+            page.text()
+        );
+
+        let title = &page.lines[0][0].coords;
+        assert_eq!((300, 300, 360), (title.x1, title.y1, title.x2));
+        // The blank line is a gap; two spaces and a tab indent.
+        let season = &page.lines[1][0].coords;
+        assert_eq!((360, 400), (season.x1, season.y1));
+        assert_eq!(300 + 8 * 30, page.lines[2][0].coords.x1);
+        assert_eq!("II.\n", book.pages[1].text());
+    }
+
+    #[test]
+    fn page_delimiters_and_full_pages() {
+        let options = PlainTextOptions {
+            page_delimiter: Some(Regex::new(r"^\[Pg \d+\]$").unwrap()),
+            lines_per_page: 2,
+        };
+        let text = "[Pg 1]\none\n[Pg 2]\ntwo\nthree\n[Pg 3]\nfour\n\n\n";
+        let book = process_text(text.as_bytes(), &options).unwrap();
+        let pages: Vec<String> = book.pages.iter().map(|p| p.text()).collect();
+        assert_eq!(vec!["one\n", "two\nthree\n", "four\n"], pages);
+    }
+
+    #[test]
+    fn empty_pages_are_skipped() {
+        let text = "\x0cone\n\x0c\x0ctwo\n";
+        let book = process_text(text.as_bytes(), &PlainTextOptions::default()).unwrap();
+        let pages: Vec<String> = book.pages.iter().map(|p| p.text()).collect();
+        assert_eq!(vec!["one\n", "two\n"], pages);
+    }
+
+    #[test]
+    fn long_lines_widen_the_page() {
+        let line = "word ".repeat(100);
+        let book = process_text(line.as_bytes(), &PlainTextOptions::default()).unwrap();
+        assert_eq!(600 + 499 * 30, book.pages[0].width);
+    }
+}
@@ -0,0 +1,346 @@
+use geometry::{line_baseline, line_bbox, BBox};
+use types::Book;
+
+/// A gap this many times the page's usual baseline spacing separates poems (or stanzas).
+const TITLE_GAP: f64 = 1.5;
+/// Titles are centered to within this fraction of the text width...
+const CENTERED_TOLERANCE: f64 = 0.05;
+/// ...and no wider than this fraction of it.
+const TITLE_MAX_WIDTH: f64 = 0.7;
+/// A title in capitals has at least this fraction of its letters uppercase.
+const CAPS_FRACTION: f64 = 0.6;
+/// Attributions are short: no wider than this fraction of the text width.
+const ATTRIBUTION_MAX_WIDTH: f64 = 0.5;
+/// Right-aligned attributions end within this fraction of the text width of the right edge.
+const RIGHT_ALIGN_TOLERANCE: f64 = 0.05;
+
+/// One poem found by ``segment_poems``.
+///
+/// Page numbers are scan indices; line numbers index into ``BookPage::in_reading_order``.
+/// ``start`` is the first line of the poem's body (after any title) and ``end`` is its last
+/// line (before any attribution), both inclusive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoemRecord {
+    pub title: Option<String>,
+    pub attribution: Option<String>,
+    pub start_page: usize,
+    pub start_line: usize,
+    pub end_page: usize,
+    pub end_line: usize,
+}
+
+/// A body line of a poetry page, with what we need to know about its surroundings.
+struct Line {
+    page: usize,
+    line: usize,
+    text: String,
+    /// Centered and narrow enough to be a title.
+    centered: bool,
+    /// Mostly capital letters.
+    caps: bool,
+    /// Short and pushed to the right, or introduced by a dash or "by".
+    attribution: bool,
+    /// Preceded by extra space on its page, or by space left at the bottom of the page before.
+    gap_before: bool,
+    base: u32,
+}
+
+fn median(mut xs: Vec<f64>) -> Option<f64> {
+    if xs.is_empty() {
+        return None;
+    }
+    xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    Some(xs[xs.len() / 2])
+}
+
+fn caps_fraction(text: &str) -> f64 {
+    let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+    if letters.is_empty() {
+        return 0.0;
+    }
+    letters.iter().filter(|c| c.is_uppercase()).count() as f64 / letters.len() as f64
+}
+
+/// Describe the body lines of one page of poetry, and the usual baseline spacing used to find
+/// gaps: the page's own, or ``carried`` over from the page before if it has too few lines.
+///
+/// The first line's ``gap_before`` is left for ``segment_poems`` to decide.
+fn describe_page(book: &Book, index: usize, carried: Option<f64>) -> (Vec<Line>, Option<f64>) {
+    let page = book.pages[index].in_reading_order();
+    let body: Vec<(usize, BBox, u32, String)> = page
+        .lines
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| !page.is_running(i))
+        .filter_map(|(i, l)| {
+            let text = l
+                .iter()
+                .map(|w| w.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            match (line_bbox(l), line_baseline(l)) {
+                (Some(b), Some(base)) => Some((i, b, base, text)),
+                _ => None,
+            }
+        })
+        .collect();
+    let text_box = match body.first() {
+        Some(first) => body.iter().fold(first.1, |acc, l| acc.union(&l.1)),
+        None => return (Vec::new(), carried),
+    };
+    let width = f64::from(text_box.width());
+    let spacing = median(
+        body.windows(2)
+            .map(|pair| f64::from(pair[1].2) - f64::from(pair[0].2))
+            .filter(|&d| d > 0.0)
+            .collect(),
+    )
+    .or(carried);
+
+    let mut lines = Vec::new();
+    let mut previous_base: Option<u32> = None;
+    for (i, b, base, text) in body {
+        let gap_before = match (previous_base, spacing) {
+            (Some(prev), Some(usual)) => (f64::from(base) - f64::from(prev)) > usual * TITLE_GAP,
+            _ => true,
+        };
+        previous_base = Some(base);
+
+        let narrow = f64::from(b.width()) <= width * TITLE_MAX_WIDTH;
+        let centered =
+            narrow && (b.center_x() - text_box.center_x()).abs() <= width * CENTERED_TOLERANCE;
+        let short = f64::from(b.width()) <= width * ATTRIBUTION_MAX_WIDTH;
+        let right_aligned = f64::from(text_box.right - b.right) <= width * RIGHT_ALIGN_TOLERANCE
+            && f64::from(b.left - text_box.left) > width * (1.0 - ATTRIBUTION_MAX_WIDTH);
+        let introduced = text.starts_with('—')
+            || text.starts_with('-')
+            || text.to_lowercase().starts_with("by ");
+        lines.push(Line {
+            page: index,
+            line: i,
+            caps: caps_fraction(&text) >= CAPS_FRACTION,
+            centered,
+            attribution: short && (right_aligned || introduced),
+            gap_before,
+            base,
+            text,
+        });
+    }
+    (lines, spacing)
+}
+
+/// Is this line set like a title, wherever it is?
+fn title_like(line: &Line) -> bool {
+    let trailing = line.text.trim_end();
+    let verse_punctuation = trailing.ends_with(',') || trailing.ends_with(';');
+    (line.centered || line.caps) && !verse_punctuation && !line.attribution
+}
+
+/// Could this line start a new poem?
+fn is_title(line: &Line) -> bool {
+    line.gap_before && title_like(line)
+}
+
+/// Split runs of poetry pages into individual poems.
+///
+/// ``is_poetry[i]`` says whether scan ``i`` was classified as poetry; a poem never spans a
+/// page that is not. Within a run, a new poem starts at a title: a line after extra space
+/// that is centered or set in capitals, and does not end like a line of verse. The top of the
+/// first page of a run counts as extra space; the top of a later page does only if the page
+/// before ended well above the lowest last line of the run, leaving the rest of it blank. Consecutive title lines ("ODE" / "ON A GRECIAN URN") are joined. A short
+/// right-aligned line, or one starting with a dash or "by", just before the next title or
+/// the end of the run is taken as the attribution of the poem it ends. Running headers are
+/// skipped, so run ``running::mark_running_lines`` first.
+pub fn segment_poems(book: &Book, is_poetry: &[bool]) -> Vec<PoemRecord> {
+    let mut poems = Vec::new();
+    let mut page = 0;
+    while page < book.pages.len() {
+        if !is_poetry.get(page).cloned().unwrap_or(false) {
+            page += 1;
+            continue;
+        }
+        let mut pages = Vec::new();
+        let mut spacing = None;
+        while page < book.pages.len() && is_poetry.get(page).cloned().unwrap_or(false) {
+            let (lines, page_spacing) = describe_page(book, page, spacing);
+            spacing = page_spacing;
+            pages.push((lines, spacing));
+            page += 1;
+        }
+
+        // Where the last line of a full page sits.
+        let bottom = pages
+            .iter()
+            .filter_map(|(l, _)| l.last())
+            .map(|l| l.base)
+            .max();
+        let mut lines: Vec<Line> = Vec::new();
+        for (mut page_lines, spacing) in pages {
+            if let Some(first) = page_lines.first_mut() {
+                first.gap_before = match (lines.last(), bottom, spacing) {
+                    (Some(last), Some(bottom), Some(usual)) => {
+                        f64::from(bottom) - f64::from(last.base) > usual * TITLE_GAP
+                    }
+                    (Some(_), _, _) => false,
+                    (None, _, _) => true,
+                };
+            }
+            lines.extend(page_lines);
+        }
+        segment_run(&lines, &mut poems);
+    }
+    poems
+}
+
+fn segment_run(lines: &[Line], poems: &mut Vec<PoemRecord>) {
+    let mut i = 0;
+    while i < lines.len() {
+        // Collect the title, if this poem has one.
+        let mut title: Vec<&str> = Vec::new();
+        if is_title(&lines[i]) {
+            title.push(&lines[i].text);
+            i += 1;
+            while i < lines.len() && !lines[i].gap_before && title_like(&lines[i]) {
+                title.push(&lines[i].text);
+                i += 1;
+            }
+        }
+        if i >= lines.len() {
+            break;
+        }
+
+        let start = i;
+        let mut end = i;
+        while end + 1 < lines.len() && !is_title(&lines[end + 1]) {
+            end += 1;
+        }
+        let next = end + 1;
+
+        let attribution = if end > start && lines[end].attribution {
+            end -= 1;
+            Some(lines[end + 1].text.clone())
+        } else {
+            None
+        };
+
+        poems.push(PoemRecord {
+            title: if title.is_empty() {
+                None
+            } else {
+                Some(title.join(" "))
+            },
+            attribution,
+            start_page: lines[start].page,
+            start_line: lines[start].line,
+            end_page: lines[end].page,
+            end_line: lines[end].line,
+        });
+        i = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::{BookPage, BookWord, WordCoords};
+
+    fn line(top: u16, x1: u16, x2: u16, text: &str) -> Vec<BookWord> {
+        vec![BookWord::new(
+            WordCoords {
+                x1,
+                y1: top,
+                x2,
+                y2: top + 40,
+                base: top + 40,
+            },
+            text.to_owned(),
+        )]
+    }
+
+    fn page(lines: Vec<Vec<BookWord>>) -> BookPage {
+        let mut p = BookPage::new();
+        p.width = 2000;
+        p.height = 3000;
+        p.dpi = 600;
+        p.lines = lines;
+        p
+    }
+
+    fn anthology() -> Book {
+        let mut book = Book::new();
+        book.pages.push(page(vec![line(
+            500,
+            200,
+            1800,
+            "Prose goes all the way across.",
+        )]));
+        book.pages.push(page(vec![
+            line(300, 800, 1200, "TO AUTUMN"),
+            line(500, 200, 1500, "Season of mists and mellow fruitfulness,"),
+            line(560, 260, 1500, "Close bosom-friend of the maturing sun;"),
+            line(620, 200, 1600, "Conspiring with him how to load and bless"),
+            line(680, 1400, 1800, "—KEATS."),
+            line(900, 900, 1100, "ODE"),
+            line(960, 700, 1300, "ON A GRECIAN URN"),
+            line(1100, 200, 1500, "Thou still unravish'd bride of quietness,"),
+        ]));
+        book.pages.push(page(vec![
+            line(
+                300,
+                200,
+                1500,
+                "Thou foster-child of silence and slow time,",
+            ),
+            line(360, 200, 1500, "Sylvan historian, who canst thus express"),
+        ]));
+        book
+    }
+
+    #[test]
+    fn splits_titled_poems() {
+        let poems = segment_poems(&anthology(), &[false, true, true]);
+        assert_eq!(2, poems.len());
+
+        assert_eq!(Some("TO AUTUMN".to_owned()), poems[0].title);
+        assert_eq!(Some("—KEATS.".to_owned()), poems[0].attribution);
+        assert_eq!((1, 1), (poems[0].start_page, poems[0].start_line));
+        assert_eq!((1, 3), (poems[0].end_page, poems[0].end_line));
+
+        assert_eq!(Some("ODE ON A GRECIAN URN".to_owned()), poems[1].title);
+        assert_eq!(None, poems[1].attribution);
+        assert_eq!((1, 7), (poems[1].start_page, poems[1].start_line));
+        assert_eq!((2, 1), (poems[1].end_page, poems[1].end_line));
+    }
+
+    #[test]
+    fn poem_spans_pages() {
+        let mut book = anthology();
+        // A verse line in capitals at the top of a page still continues the poem.
+        book.pages[2]
+            .lines
+            .insert(0, line(240, 600, 1400, "O ATTIC SHAPE! FAIR ATTITUDE!"));
+        let poems = segment_poems(&book, &[false, true, true]);
+        assert_eq!(2, poems.len());
+        assert_eq!((2, 2), (poems[1].end_page, poems[1].end_line));
+
+        // A page that ends early is followed by a new poem.
+        book.pages.push(page(vec![
+            line(300, 800, 1200, "LA BELLE DAME"),
+            line(500, 200, 1500, "O what can ail thee, knight-at-arms,"),
+            line(560, 200, 1500, "Alone and palely loitering?"),
+        ]));
+        let poems = segment_poems(&book, &[false, true, true, true]);
+        assert_eq!(3, poems.len());
+        assert_eq!((2, 2), (poems[1].end_page, poems[1].end_line));
+        assert_eq!(Some("LA BELLE DAME".to_owned()), poems[2].title);
+        assert_eq!((3, 1), (poems[2].start_page, poems[2].start_line));
+    }
+
+    #[test]
+    fn stops_at_prose() {
+        let poems = segment_poems(&anthology(), &[false, true, false]);
+        assert_eq!(2, poems.len());
+        assert_eq!((1, 7), (poems[1].end_page, poems[1].end_line));
+        assert!(segment_poems(&anthology(), &[]).is_empty());
+    }
+}
@@ -0,0 +1,327 @@
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read};
+use text::{tokenize_with, Normalizer};
+use types::{Book, BookPage};
+
+/// Words that appear at least this many times in a book are part of its own vocabulary.
+const MIN_BOOK_COUNT: usize = 3;
+/// A word taller than this many times the page's median word height is misplaced.
+const MAX_HEIGHT_RATIO: f64 = 4.0;
+/// Neighboring words overlapping by more than this fraction of the narrower one are misplaced.
+const MAX_OVERLAP: f64 = 0.5;
+/// Confidence proxy of a plausible word that is not in the lexicon.
+const PLAUSIBLE_CONFIDENCE: f64 = 0.5;
+/// Name prefixes that may be followed by a capital, as in McDonald, MacLeod and O'Brien.
+const NAME_PREFIXES: &[&str] = &["Mc", "Mac", "O'", "O\u{2019}"];
+/// Punctuation that stands alone as a word in verse and contents pages, such as dashes,
+/// ampersands and quote marks set apart.
+const STANDALONE_PUNCTUATION: &[&str] = &[
+    "—", "——", "–", "-", "&", "&c.", "*", "†", "‡", "§", "¶", "\"", "“", "”", "'", "‘", "’", "(",
+    ")", "[", "]", ",", ";", ":", ".", "!", "?",
+];
+
+lazy_static! {
+    /// Numbers that may end in letters: ordinals like ``1st`` and ``2d``, and book formats
+    /// like ``8vo`` and ``12mo``.
+    static ref NUMBER_SUFFIX: Regex = Regex::new(r"^(?i)\d+(st|nd|rd|th|d|vo|to|mo)$").unwrap();
+}
+
+/// Known words, to tell real OCR output from garbage.
+///
+/// Old books use spellings no modern dictionary has, so the most useful lexicon is usually
+/// the book's own vocabulary: garbage OCR rarely repeats itself. A word list can be added on
+/// top with ``load``.
+#[derive(Debug, Default, Clone)]
+pub struct Lexicon {
+    words: HashSet<String>,
+}
+
+impl Lexicon {
+    pub fn new() -> Lexicon {
+        Lexicon::default()
+    }
+    /// Every token that appears at least ``MIN_BOOK_COUNT`` times in the book.
+    pub fn from_book(book: &Book) -> Lexicon {
+        Lexicon::from_book_with(book, &Normalizer::all())
+    }
+    /// ``from_book``, tokenizing after some other cleanup.
+    pub fn from_book_with(book: &Book, normalizer: &Normalizer) -> Lexicon {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for page in &book.pages {
+            for word in page.lines.iter().flat_map(|l| l.iter()) {
+                for token in tokenize_with(normalizer, &word.text) {
+                    *counts.entry(token).or_insert(0) += 1;
+                }
+            }
+        }
+        Lexicon {
+            words: counts
+                .into_iter()
+                .filter(|&(_, n)| n >= MIN_BOOK_COUNT)
+                .map(|(w, _)| w)
+                .collect(),
+        }
+    }
+    /// Add the words of a list with one word per line.
+    pub fn load(&mut self, path: &str) -> io::Result<()> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        let normalizer = Normalizer::all();
+        self.words.extend(
+            text.lines()
+                .map(|l| normalizer.normalize(l.trim()).to_lowercase())
+                .filter(|w| !w.is_empty()),
+        );
+        Ok(())
+    }
+    pub fn insert(&mut self, word: &str) {
+        self.words.insert(word.to_lowercase());
+    }
+    pub fn contains(&self, token: &str) -> bool {
+        self.words.contains(token)
+    }
+}
+
+/// Estimates of how well a page was recognized, each a fraction of its words.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OcrQuality {
+    pub words: usize,
+    /// Words whose tokens are all known words.
+    pub dictionary_hits: f64,
+    /// Words that cannot be text: mostly symbols, letters mixed with digits, or long repeats.
+    /// A dash, ampersand or other common mark standing alone is not garbage.
+    pub garbage_ratio: f64,
+    /// Mean of a per-word confidence: 1 for known words, 0.5 for plausible ones, 0 for garbage.
+    pub mean_confidence: f64,
+    /// Words that are empty, outside the page, far too tall, or on top of their neighbor,
+    /// plus words whose coordinates were lost.
+    pub coord_anomalies: f64,
+}
+
+/// Could this be a word at all?
+pub fn is_garbage(word: &str) -> bool {
+    let chars: Vec<char> = word.trim().chars().collect();
+    if chars.is_empty() || STANDALONE_PUNCTUATION.contains(&word.trim()) {
+        return false;
+    }
+    let alphanumeric = chars.iter().filter(|c| c.is_alphanumeric()).count();
+    if alphanumeric * 2 < chars.len() {
+        return true;
+    }
+    let core = word.trim_matches(|c: char| !c.is_alphanumeric());
+    let letters = chars.iter().any(|c| c.is_alphabetic());
+    let digits = chars.iter().any(|c| c.is_numeric());
+    if letters && digits && !NUMBER_SUFFIX.is_match(core) {
+        return true;
+    }
+    // "tHe", "maN": a capital after a lowercase letter, except right after a name prefix.
+    let name = NAME_PREFIXES
+        .iter()
+        .filter_map(|p| core.strip_prefix(p))
+        .find(|rest| rest.starts_with(char::is_uppercase));
+    let rest: Vec<char> = name.unwrap_or(core).chars().collect();
+    let case_flips = rest
+        .windows(2)
+        .filter(|w| w[0].is_lowercase() && w[1].is_uppercase())
+        .count();
+    if case_flips > 0 {
+        return true;
+    }
+    chars.windows(4).any(|w| w.iter().all(|&c| c == w[0]))
+}
+
+/// A word that is made of letters and has a vowel looks like it could be real.
+fn is_plausible(token: &str) -> bool {
+    token.chars().any(|c| "aeiouyàâäéèêëîïôöûüæœ".contains(c))
+}
+
+fn median(mut xs: Vec<u32>) -> Option<u32> {
+    if xs.is_empty() {
+        return None;
+    }
+    xs.sort();
+    Some(xs[xs.len() / 2])
+}
+
+/// Count words whose coordinates cannot be right.
+fn coord_anomalies(page: &BookPage) -> usize {
+    let median_height = median(
+        page.lines
+            .iter()
+            .flat_map(|l| l.iter())
+            .map(|w| w.bbox().height())
+            .filter(|&h| h > 0)
+            .collect(),
+    );
+    let mut anomalies = 0;
+    for line in &page.lines {
+        for (i, word) in line.iter().enumerate() {
+            let b = word.bbox();
+            let empty = b.width() == 0 || b.height() == 0;
+            let outside = (page.width > 0 && b.right > page.width)
+                || (page.height > 0 && b.bottom > page.height);
+            let tall = median_height
+                .map(|m| f64::from(b.height()) > f64::from(m) * MAX_HEIGHT_RATIO)
+                .unwrap_or(false);
+            let overlaps = i > 0 && {
+                let previous = line[i - 1].bbox();
+                let narrower = b.width().min(previous.width());
+                match previous.intersection(&b) {
+                    Some(shared) => {
+                        narrower > 0
+                            && f64::from(shared.width()) > f64::from(narrower) * MAX_OVERLAP
+                    }
+                    None => false,
+                }
+            };
+            if empty || outside || tall || overlaps {
+                anomalies += 1;
+            }
+        }
+    }
+    anomalies
+}
+
+/// Estimate the OCR quality of a page; ``known`` decides which lowercase tokens are words.
+pub fn page_quality<F: Fn(&str) -> bool>(page: &BookPage, known: F) -> OcrQuality {
+    page_quality_with(page, &Normalizer::all(), known)
+}
+
+/// ``page_quality``, tokenizing after some other cleanup.
+pub fn page_quality_with<F: Fn(&str) -> bool>(
+    page: &BookPage,
+    normalizer: &Normalizer,
+    known: F,
+) -> OcrQuality {
+    let mut words = 0;
+    let mut hits = 0;
+    let mut garbage = 0;
+    let mut confidence = 0.0;
+    for word in page.lines.iter().flat_map(|l| l.iter()) {
+        words += 1;
+        let tokens = tokenize_with(normalizer, &word.text);
+        if is_garbage(&word.text) {
+            garbage += 1;
+        } else if !tokens.is_empty() && tokens.iter().all(|t| known(t)) {
+            hits += 1;
+            confidence += 1.0;
+        } else if !tokens.is_empty() && tokens.iter().all(|t| is_plausible(t)) {
+            confidence += PLAUSIBLE_CONFIDENCE;
+        }
+    }
+    let anomalies = coord_anomalies(page) + page.bad_coords as usize;
+    let total = words + page.bad_coords as usize;
+    let fraction = |n: f64, d: usize| if d == 0 { 0.0 } else { n / d as f64 };
+    OcrQuality {
+        words,
+        dictionary_hits: fraction(hits as f64, words),
+        garbage_ratio: fraction(garbage as f64, words),
+        mean_confidence: fraction(confidence, words),
+        coord_anomalies: fraction(anomalies as f64, total),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::{BookWord, WordCoords};
+
+    fn page(texts: &[&str]) -> BookPage {
+        let mut page = BookPage::new();
+        page.width = 2000;
+        page.height = 3000;
+        page.lines.push(
+            texts
+                .iter()
+                .enumerate()
+                .map(|(i, t)| {
+                    let x1 = 100 + 200 * i as u16;
+                    BookWord::new(
+                        WordCoords {
+                            x1,
+                            y1: 140,
+                            x2: x1 + 150,
+                            y2: 100,
+                            base: 140,
+                        },
+                        t.to_string(),
+                    )
+                })
+                .collect(),
+        );
+        page
+    }
+
+    #[test]
+    fn garbage() {
+        assert!(is_garbage("l0ve"));
+        assert!(is_garbage("tHe"));
+        assert!(is_garbage("~*;^"));
+        assert!(is_garbage("mmmmm"));
+        assert!(!is_garbage("O'er"));
+        assert!(!is_garbage("1889."));
+        assert!(!is_garbage("bosom-friend,"));
+        assert!(!is_garbage("McDonald"));
+        assert!(!is_garbage("MacLeod,"));
+        assert!(!is_garbage("O\u{2019}Brien"));
+        assert!(is_garbage("McDoNald"));
+        assert!(is_garbage("MacleOd"));
+        assert!(!is_garbage("1st"));
+        assert!(!is_garbage("22nd,"));
+        assert!(!is_garbage("8vo."));
+        assert!(!is_garbage("12mo"));
+        assert!(is_garbage("st1"));
+        assert!(is_garbage("8vox"));
+        assert!(!is_garbage("—"));
+        assert!(!is_garbage(" & "));
+        assert!(!is_garbage("&c."));
+        assert!(!is_garbage("“"));
+        assert!(is_garbage("—&"));
+    }
+
+    #[test]
+    fn clean_and_dirty_pages() {
+        let mut lexicon = Lexicon::new();
+        for w in &["the", "night", "is", "long"] {
+            lexicon.insert(w);
+        }
+        let clean = page_quality(&page(&["The", "night", "is", "long."]), |t| {
+            lexicon.contains(t)
+        });
+        assert_eq!(4, clean.words);
+        assert_eq!(1.0, clean.dictionary_hits);
+        assert_eq!(0.0, clean.garbage_ratio);
+        assert_eq!(1.0, clean.mean_confidence);
+        assert_eq!(0.0, clean.coord_anomalies);
+
+        let dirty = page_quality(&page(&["Tlie", "n1ght", "~;^", "lgng"]), |t| {
+            lexicon.contains(t)
+        });
+        assert_eq!(0.0, dirty.dictionary_hits);
+        assert_eq!(0.5, dirty.garbage_ratio);
+        assert_eq!(0.125, dirty.mean_confidence);
+    }
+
+    #[test]
+    fn misplaced_words() {
+        let mut p = page(&["one", "two", "three"]);
+        p.lines[0][1].coords.x1 = 110;
+        p.lines[0][1].coords.x2 = 250;
+        p.lines[0][2].coords.x2 = 2500;
+        p.bad_coords = 1;
+        let quality = page_quality(&p, |_| true);
+        assert_eq!(0.75, quality.coord_anomalies);
+    }
+
+    #[test]
+    fn book_vocabulary() {
+        let mut book = Book::new();
+        book.pages
+            .push(page(&["rose", "rose", "r0se", "rose", "thorn"]));
+        let lexicon = Lexicon::from_book(&book);
+        assert!(lexicon.contains("rose"));
+        assert!(!lexicon.contains("thorn"));
+    }
+}
@@ -0,0 +1,272 @@
+use geometry::{line_bbox, BBox};
+use std::cmp::{max, min};
+use types::{Book, BookPage, BookWord};
+
+/// Only this many lines at the top (or bottom) of a page can be running lines.
+const CANDIDATE_LINES: usize = 2;
+/// Running lines sit within this fraction of the page height from the top or bottom edge.
+const EDGE_FRACTION: f64 = 0.15;
+/// Compare each page against this many pages on either side (headers alternate recto/verso).
+const NEIGHBOR_PAGES: usize = 4;
+/// Two running lines of the same kind start within this fraction of the page height.
+const POSITION_TOLERANCE: f64 = 0.05;
+/// Normalized texts at least this similar are the same running line.
+const MIN_SIMILARITY: f64 = 0.8;
+
+/// Reduce a line to what should repeat from page to page.
+///
+/// Case and punctuation are OCR-noisy, and page numbers change on every page, so we keep
+/// lowercase letters and collapse every run of digits to ``#``. Tokens are sorted, since
+/// facing pages often put the page number on opposite sides of the same header.
+pub fn normalize_line(line: &[BookWord]) -> String {
+    let mut key = String::new();
+    let mut in_digits = false;
+    for c in line.iter().flat_map(|w| w.text.chars().chain(Some(' '))) {
+        if c.is_ascii_digit() {
+            if !in_digits {
+                key.push('#');
+            }
+            in_digits = true;
+            continue;
+        }
+        in_digits = false;
+        if c.is_alphabetic() {
+            key.extend(c.to_lowercase());
+        } else if c.is_whitespace() && !key.ends_with(' ') && !key.is_empty() {
+            key.push(' ');
+        }
+    }
+    let mut tokens: Vec<&str> = key.split_whitespace().collect();
+    tokens.sort();
+    tokens.join(" ")
+}
+
+/// Similarity of two strings in ``[0,1]``: one minus their edit distance over the longer length.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = max(a.len(), b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = previous[j] + if ca == cb { 0 } else { 1 };
+            current[j + 1] = min(substitute, min(previous[j + 1], current[j]) + 1);
+        }
+        previous = current;
+    }
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
+
+/// A line near the top or bottom of a page that might repeat on other pages.
+struct Candidate {
+    line: usize,
+    top: f64,
+    key: String,
+}
+
+/// The first (or last) few lines of a page, if they sit near that edge, closest to it first.
+pub(crate) fn edge_lines(page: &BookPage, from_top: bool) -> Vec<usize> {
+    if page.height == 0 {
+        return Vec::new();
+    }
+    let height = f64::from(page.height);
+    let mut lines: Vec<(usize, BBox)> = page
+        .lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, l)| line_bbox(l).map(|b| (i, b)))
+        .collect();
+    lines.sort_by_key(|&(_, b)| b.top);
+    if !from_top {
+        lines.reverse();
+    }
+    lines
+        .into_iter()
+        .take(CANDIDATE_LINES)
+        .filter(|&(_, b)| {
+            if from_top {
+                f64::from(b.bottom) <= height * EDGE_FRACTION
+            } else {
+                f64::from(b.top) >= height * (1.0 - EDGE_FRACTION)
+            }
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+fn candidates(page: &BookPage, from_top: bool) -> Vec<Candidate> {
+    edge_lines(page, from_top)
+        .into_iter()
+        .filter_map(|i| {
+            line_bbox(&page.lines[i]).map(|b| Candidate {
+                line: i,
+                top: f64::from(b.top) / f64::from(page.height),
+                key: normalize_line(&page.lines[i]),
+            })
+        })
+        .filter(|c| !c.key.is_empty())
+        .collect()
+}
+
+/// Which candidates on each page have a look-alike in the same place on a nearby page.
+fn repeated(all: &[Vec<Candidate>]) -> Vec<Vec<usize>> {
+    (0..all.len())
+        .map(|p| {
+            let lo = p.saturating_sub(NEIGHBOR_PAGES);
+            let hi = min(all.len(), p + NEIGHBOR_PAGES + 1);
+            let mut found: Vec<usize> = all[p]
+                .iter()
+                .filter(|c| {
+                    (lo..hi).filter(|&q| q != p).any(|q| {
+                        all[q].iter().any(|other| {
+                            (other.top - c.top).abs() <= POSITION_TOLERANCE
+                                && similarity(&other.key, &c.key) >= MIN_SIMILARITY
+                        })
+                    })
+                })
+                .map(|c| c.line)
+                .collect();
+            found.sort();
+            found
+        })
+        .collect()
+}
+
+/// Find running headers and footers across a whole book and mark them on each page.
+///
+/// A line is a running header if it is one of the first lines on its page, close to the top
+/// edge, and a similar line (after ``normalize_line``) sits in the same place on one of the
+/// neighboring pages. Footers are the same from the bottom edge. Page numbers on their own
+/// line count, since they all normalize to ``#``.
+pub fn mark_running_lines(book: &mut Book) {
+    let tops: Vec<Vec<Candidate>> = book.pages.iter().map(|p| candidates(p, true)).collect();
+    let bottoms: Vec<Vec<Candidate>> = book.pages.iter().map(|p| candidates(p, false)).collect();
+    let headers = repeated(&tops);
+    let footers = repeated(&bottoms);
+    for ((page, header), footer) in book.pages.iter_mut().zip(headers).zip(footers) {
+        page.footer_lines = footer.into_iter().filter(|i| !header.contains(i)).collect();
+        page.header_lines = header;
+    }
+}
+
+impl BookPage {
+    pub fn is_running(&self, line: usize) -> bool {
+        self.header_lines.contains(&line) || self.footer_lines.contains(&line)
+    }
+    /// A copy of this page without its running headers and footers.
+    pub fn without_running(&self) -> BookPage {
+        BookPage {
+            lines: self
+                .lines
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| !self.is_running(i))
+                .map(|(_, l)| l.clone())
+                .collect(),
+            header_lines: Vec::new(),
+            footer_lines: Vec::new(),
+            ..self.clone()
+        }
+    }
+    fn marked_text(&self, marks: &[usize]) -> Option<String> {
+        if marks.is_empty() {
+            return None;
+        }
+        let lines: Vec<String> = marks
+            .iter()
+            .map(|&i| {
+                self.lines[i]
+                    .iter()
+                    .map(|w| w.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+    /// The running header of this page, if one was marked.
+    pub fn header_text(&self) -> Option<String> {
+        self.marked_text(&self.header_lines)
+    }
+    /// The running footer of this page, if one was marked.
+    pub fn footer_text(&self) -> Option<String> {
+        self.marked_text(&self.footer_lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::WordCoords;
+
+    fn line(top: u16, texts: &[&str]) -> Vec<BookWord> {
+        texts
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                let x1 = 200 + 300 * i as u16;
+                BookWord::new(
+                    WordCoords {
+                        x1,
+                        y1: top,
+                        x2: x1 + 250,
+                        y2: top + 50,
+                        base: top + 50,
+                    },
+                    t.to_string(),
+                )
+            })
+            .collect()
+    }
+
+    fn page(lines: Vec<Vec<BookWord>>) -> BookPage {
+        let mut p = BookPage::new();
+        p.width = 2000;
+        p.height = 3000;
+        p.dpi = 600;
+        p.lines = lines;
+        p
+    }
+
+    #[test]
+    fn normalization() {
+        assert_eq!("# poems", normalize_line(&line(0, &["46", "POEMS."])));
+        assert_eq!("# poems", normalize_line(&line(0, &["Poems,", "147"])));
+        assert_eq!(1.0, similarity("poems", "poems"));
+        assert!(similarity("# poems", "# poem5") >= MIN_SIMILARITY);
+        assert!(similarity("# poems", "the night") < MIN_SIMILARITY);
+    }
+
+    #[test]
+    fn marks_headers_and_page_numbers() {
+        let mut book = Book::new();
+        book.pages.push(page(vec![
+            line(100, &["46", "POEMS."]),
+            line(1000, &["The", "night"]),
+            line(2900, &["ix"]),
+        ]));
+        book.pages.push(page(vec![
+            line(1000, &["is", "long"]),
+            line(100, &["POEMS,", "47"]),
+            line(2900, &["x"]),
+        ]));
+        book.pages.push(page(vec![line(1000, &["Chapter"])]));
+        mark_running_lines(&mut book);
+
+        assert_eq!(vec![0], book.pages[0].header_lines);
+        assert_eq!(vec![1], book.pages[1].header_lines);
+        assert!(book.pages[2].header_lines.is_empty());
+        assert_eq!(Some("46 POEMS.".to_owned()), book.pages[0].header_text());
+        // Roman numerals are different words, but short enough to stay unmarked.
+        assert!(book.pages[0].footer_lines.is_empty());
+
+        let body = book.pages[1].without_running();
+        assert_eq!("is\tlong\nx\n", body.text());
+        // Marks follow their lines into reading order.
+        assert_eq!(vec![0], book.pages[1].in_reading_order().header_lines);
+    }
+}
@@ -1,4 +1,7 @@
 use regex::Regex;
+use types::{Book, BookPage};
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
 
 lazy_static! {
     static ref NOT_WORD: Regex = Regex::new(r"\W").unwrap();
@@ -6,12 +9,174 @@ lazy_static! {
     static ref ONLY_DIGITS: Regex = Regex::new(r"^\d+$").unwrap();
 }
 
+/// Which Unicode normalization form to apply before any other cleanup.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnicodeForm {
+    Unchanged,
+    /// Canonical composition: combining accents are merged into their letters.
+    Nfc,
+    /// Compatibility composition: also folds ligatures, long s, superscripts and fractions.
+    Nfkc,
+}
+
+/// Which cleanup steps to apply to OCR text from old books.
+///
+/// Old typography survives OCR as characters that modern word lists never contain, so
+/// "ﬁrſt" and "first" count as different words unless we fold them together.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Normalizer {
+    pub form: UnicodeForm,
+    /// Read the long s (ſ) as s.
+    pub long_s: bool,
+    /// Split typographic ligatures (ﬁ, ﬂ, ﬀ, ﬃ, ﬄ, ﬅ, ﬆ) into their letters.
+    pub ligatures: bool,
+    /// Replace curly and low quotes and primes with ``'`` and ``"``.
+    pub quotes: bool,
+}
+
+impl Normalizer {
+    /// Every cleanup, with canonical (not compatibility) composition.
+    pub fn all() -> Normalizer {
+        Normalizer {
+            form: UnicodeForm::Nfc,
+            long_s: true,
+            ligatures: true,
+            quotes: true,
+        }
+    }
+    /// Leave text exactly as the OCR gave it.
+    pub fn none() -> Normalizer {
+        Normalizer {
+            form: UnicodeForm::Unchanged,
+            long_s: false,
+            ligatures: false,
+            quotes: false,
+        }
+    }
+    pub fn normalize(&self, input: &str) -> String {
+        let composed: String = match self.form {
+            UnicodeForm::Unchanged => input.to_owned(),
+            UnicodeForm::Nfc => input.nfc().collect(),
+            UnicodeForm::Nfkc => input.nfkc().collect(),
+        };
+        let mut output = String::with_capacity(composed.len());
+        for c in composed.chars() {
+            match c {
+                'ſ' if self.long_s => output.push('s'),
+                'ﬀ' if self.ligatures => output.push_str("ff"),
+                'ﬁ' if self.ligatures => output.push_str("fi"),
+                'ﬂ' if self.ligatures => output.push_str("fl"),
+                'ﬃ' if self.ligatures => output.push_str("ffi"),
+                'ﬄ' if self.ligatures => output.push_str("ffl"),
+                'ﬅ' | 'ﬆ' if self.ligatures => output.push_str("st"),
+                '‘' | '’' | '‚' | '‛' | '′' if self.quotes => output.push('\''),
+                '“' | '”' | '„' | '‟' | '″' if self.quotes => output.push('"'),
+                c => output.push(c),
+            }
+        }
+        output
+    }
+}
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        Normalizer::all()
+    }
+}
+
+impl BookPage {
+    /// Normalize the text of every word on this page in place.
+    pub fn normalize_text(&mut self, normalizer: &Normalizer) {
+        for word in self.lines.iter_mut().flat_map(|l| l.iter_mut()) {
+            word.text = normalizer.normalize(&word.text);
+        }
+    }
+}
+
+impl Book {
+    /// Normalize the text of every word in this book in place.
+    pub fn normalize_text(&mut self, normalizer: &Normalizer) {
+        for page in &mut self.pages {
+            page.normalize_text(normalizer);
+        }
+    }
+}
+
+/// Elided forms that keep their leading apostrophe, so "'tis" is not read as "tis".
+const LEADING_ELISIONS: &[&str] = &[
+    "tis", "twas", "twere", "twill", "twould", "gainst", "neath", "mid", "midst", "tween", "twixt",
+    "em",
+];
+
+fn is_word(segment: &str) -> bool {
+    segment.chars().any(|c| c.is_alphanumeric())
+}
+
+fn is_hyphen(segment: &str) -> bool {
+    segment == "-" || segment == "\u{2010}"
+}
+
+/// Split text into lowercase word tokens, the same way for every tool.
+///
+/// Words are found with Unicode word segmentation after ``Normalizer::all``, so contractions
+/// and archaic forms ("don't", "o'er", "e'en") stay whole. A few elisions keep their leading
+/// apostrophe ("'tis", "'gainst"), and words joined by a single hyphen are kept as one
+/// compound ("bosom-friend"). Punctuation and whitespace are dropped.
+pub fn tokenize(input: &str) -> Vec<String> {
+    tokenize_with(&Normalizer::all(), input)
+}
+
+/// ``tokenize`` after some other cleanup, so features see the text the user asked for.
+pub fn tokenize_with(normalizer: &Normalizer, input: &str) -> Vec<String> {
+    let normalized = normalizer.normalize(input);
+    let segments: Vec<&str> = normalized.split_word_bounds().collect();
+    let mut tokens: Vec<String> = Vec::new();
+    // The segment index that ended the last token, so we know what a hyphen is attached to.
+    let mut last_end: Option<usize> = None;
+    let mut i = 0;
+    while i < segments.len() {
+        let segment = segments[i];
+        if is_hyphen(segment)
+            && last_end == Some(i.wrapping_sub(1))
+            && i + 1 < segments.len()
+            && is_word(segments[i + 1])
+        {
+            let last = tokens.last_mut().unwrap();
+            last.push('-');
+            last.push_str(&segments[i + 1].to_lowercase());
+            last_end = Some(i + 1);
+            i += 2;
+            continue;
+        }
+        if is_word(segment) {
+            let mut token = segment.to_lowercase();
+            let apostrophe = i > 0 && segments[i - 1] == "'";
+            let starts_word = i < 2 || !is_word(segments[i - 2]);
+            if apostrophe && starts_word && LEADING_ELISIONS.contains(&token.as_str()) {
+                token.insert(0, '\'');
+            }
+            tokens.push(token);
+            last_end = Some(i);
+        }
+        i += 1;
+    }
+    tokens
+}
+
 pub fn is_numeric(input: &str) -> bool {
     ONLY_DIGITS.is_match(input)
 }
 
+/// Reduce a word to lowercase letters and digits for matching against word lists.
+///
+/// Ligatures, long s and composed accents are folded with ``Normalizer::all`` first.
 pub fn clean_word(input: &str) -> Option<String> {
-    let x1 = input.to_lowercase();
+    clean_word_with(&Normalizer::all(), input)
+}
+
+/// ``clean_word`` after some other cleanup.
+pub fn clean_word_with(normalizer: &Normalizer, input: &str) -> Option<String> {
+    let x1 = normalizer.normalize(input).to_lowercase();
     let x2 = NOT_WORD.replace_all(x1.as_ref(), "");
     let x3 = PUNCT.replace_all(x2.as_ref(), "");
     let x4 = x3.as_ref().trim();
@@ -49,4 +214,54 @@ mod tests {
     fn empty_result() {
         assert_eq!(None, clean_word(" ? "));
     }
+
+    #[test]
+    fn old_typography() {
+        assert_eq!("first", clean_word("ﬁrſt").unwrap());
+        assert_eq!("flower", clean_word("ﬂower,").unwrap());
+        assert_eq!(
+            "\"'Tis so,\" said he.",
+            Normalizer::all().normalize("“’Tis so,” said he.")
+        );
+        assert_eq!("ﬁrſt", Normalizer::none().normalize("ﬁrſt"));
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(
+            vec![
+                "o'er",
+                "the",
+                "hills",
+                "'tis",
+                "bosom-friend's",
+                "e'en",
+                "don't"
+            ],
+            tokenize("O’er the hills, ’tis bosom-friend’s e'en — don't!")
+        );
+        assert_eq!(vec!["beau"], tokenize("beau-"));
+        assert_eq!(
+            vec!["'twas", "said", "hello"],
+            tokenize("'Twas said: 'hello'")
+        );
+        assert!(tokenize(" -- ; ").is_empty());
+        assert_eq!(vec!["ﬁrſt"], tokenize_with(&Normalizer::none(), "ﬁrſt"));
+        assert_eq!(
+            Some("ﬁrſt".to_owned()),
+            clean_word_with(&Normalizer::none(), "ﬁrſt!")
+        );
+    }
+
+    #[test]
+    fn unicode_forms() {
+        let decomposed = "cafe\u{301}";
+        assert_eq!("café", Normalizer::all().normalize(decomposed));
+        let nfkc = Normalizer {
+            form: UnicodeForm::Nfkc,
+            ..Normalizer::none()
+        };
+        assert_eq!("first", nfkc.normalize("ﬁrſt"));
+        assert_eq!(decomposed, Normalizer::none().normalize(decomposed));
+    }
 }
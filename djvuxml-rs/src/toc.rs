@@ -0,0 +1,366 @@
+use geometry::{line_bbox, BBox};
+use pagenum::{parse_label, PageLabel};
+use types::{Book, BookPage, BookWord};
+
+/// A page is a table of contents if at least this fraction of its lines are entries...
+const MIN_ENTRY_FRACTION: f64 = 0.4;
+/// ...and it has at least this many of them.
+const MIN_ENTRIES: usize = 4;
+/// Page numbers in a table of contents end within this fraction of the text width of the
+/// rightmost line.
+const RIGHT_ALIGN_TOLERANCE: f64 = 0.05;
+
+/// One line of a table of contents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TocEntry {
+    pub title: String,
+    pub author: Option<String>,
+    /// The page number as printed in the contents.
+    pub printed: PageLabel,
+    /// The scan index of the contents page this entry was found on.
+    pub toc_page: usize,
+    /// The scan index of the page carrying ``printed``, if we found it.
+    pub target: Option<usize>,
+}
+
+/// Characters that OCR makes of dot leaders.
+const LEADER_CHARS: &[char] = &['.', '·', '…', '_', '-'];
+
+/// Dot leaders come out of OCR as runs of periods, middle dots, or spaced-out dots; a lone
+/// ``-`` or ``.`` is punctuation, not a leader.
+fn is_leader(token: &str) -> bool {
+    let mut run = 0;
+    for c in token.chars() {
+        if LEADER_CHARS.contains(&c) {
+            run += 1;
+        } else if !c.is_whitespace() {
+            return false;
+        }
+    }
+    run >= 2
+}
+
+fn strip_leaders(token: &str) -> &str {
+    let trimmed = token.trim_end_matches(LEADER_CHARS);
+    // Keep a single final period ("Ode."), but not a trail of them.
+    if token[trimmed.len()..].chars().count() >= 2 {
+        trimmed
+    } else {
+        token
+    }
+}
+
+/// The words of a line that ends in a right-aligned page number, split into the title (and
+/// author, if dot leaders separate one) and the number.
+struct RawEntry {
+    title: Vec<String>,
+    author: Vec<String>,
+    printed: PageLabel,
+    leaders: bool,
+}
+
+fn parse_line(line: &[BookWord], right_edge: u32, tolerance: u32) -> Option<RawEntry> {
+    let (last, rest) = line.split_last()?;
+    if last.bbox().right + tolerance < right_edge {
+        return None;
+    }
+    let printed = parse_label(&last.text)?;
+
+    let mut before: Vec<String> = Vec::new();
+    let mut after: Vec<String> = Vec::new();
+    let mut leaders = false;
+    for word in rest {
+        if is_leader(&word.text) {
+            leaders = true;
+            continue;
+        }
+        let stripped = strip_leaders(&word.text);
+        let target = if leaders { &mut after } else { &mut before };
+        target.push(stripped.to_owned());
+        if stripped.len() != word.text.len() {
+            leaders = true;
+        }
+    }
+    if before.is_empty() {
+        return None;
+    }
+
+    // "Title, by Author" without leaders.
+    if after.is_empty() {
+        if let Some(by) = before.iter().position(|w| w == "by" || w == "By") {
+            if by > 0 && by + 1 < before.len() {
+                after = before.split_off(by + 1);
+                before.pop();
+            }
+        }
+    }
+    Some(RawEntry {
+        title: before,
+        author: after,
+        printed,
+        leaders,
+    })
+}
+
+/// Is this line without a page number set like a section heading: centered, or in capitals?
+fn is_heading(words: &[String], line: BBox, text: BBox, tolerance: u32) -> bool {
+    let centered = (line.center_x() - text.center_x()).abs() <= f64::from(tolerance)
+        && line.left > text.left + tolerance;
+    let letters: Vec<char> = words
+        .iter()
+        .flat_map(|w| w.chars())
+        .filter(|c| c.is_alphabetic())
+        .collect();
+    let caps = !letters.is_empty() && letters.iter().all(|c| c.is_uppercase());
+    centered || caps
+}
+
+fn join(words: &[String]) -> String {
+    words.join(" ").trim_end_matches(',').to_owned()
+}
+
+/// Parse the entries of a page, if it looks like a table of contents.
+///
+/// Entries are lines whose last word is a page number aligned with the right edge of the
+/// text. A line without a number directly above an entry is taken as the first part of a
+/// long title if it starts where the entry does (or where entries do) and is neither
+/// centered nor in capitals, which would make it a section heading like "SONNETS". A page
+/// qualifies when enough of its lines are entries; dot leaders count in favor, since they
+/// rarely appear anywhere else.
+pub fn parse_toc_page(page: &BookPage, toc_page: usize) -> Vec<TocEntry> {
+    let body = page.without_running().in_reading_order();
+    let text = match body.text_bbox() {
+        Some(text) => text,
+        None => return Vec::new(),
+    };
+    let tolerance = (f64::from(text.width()) * RIGHT_ALIGN_TOLERANCE) as u32;
+
+    let mut entries = Vec::new();
+    let mut leaders = 0;
+    // The words and left edge of the line before, if it could start a long title.
+    let mut pending: Option<(Vec<String>, u32)> = None;
+    for line in &body.lines {
+        match parse_line(line, text.right, tolerance) {
+            Some(mut raw) => {
+                if raw.leaders {
+                    leaders += 1;
+                }
+                let left = line_bbox(line).map_or(text.left, |b| b.left);
+                if let Some((mut words, start)) = pending.take() {
+                    let aligned = |x: u32| start.max(x) - start.min(x) <= tolerance;
+                    if aligned(left) || aligned(text.left) {
+                        words.append(&mut raw.title);
+                        raw.title = words;
+                    }
+                }
+                entries.push(TocEntry {
+                    title: join(&raw.title),
+                    author: if raw.author.is_empty() {
+                        None
+                    } else {
+                        Some(join(&raw.author))
+                    },
+                    printed: raw.printed,
+                    toc_page,
+                    target: None,
+                });
+            }
+            None => {
+                let words: Vec<String> = line.iter().map(|w| w.text.clone()).collect();
+                let label = words.len() == 1
+                    && matches!(
+                        words[0].to_lowercase().trim_matches('.'),
+                        "contents" | "page" | "index"
+                    );
+                pending = match line_bbox(line) {
+                    Some(b) if !label && !is_heading(&words, b, text, tolerance) => {
+                        Some((words, b.left))
+                    }
+                    _ => None,
+                };
+            }
+        }
+    }
+
+    let num_lines = body.lines.iter().filter(|l| line_bbox(l).is_some()).count();
+    let needed = if leaders > 0 {
+        MIN_ENTRY_FRACTION / 2.0
+    } else {
+        MIN_ENTRY_FRACTION
+    };
+    if entries.len() >= MIN_ENTRIES && entries.len() as f64 >= needed * num_lines as f64 {
+        entries
+    } else {
+        Vec::new()
+    }
+}
+
+/// Find every table of contents in a book and link its entries to scan pages.
+///
+/// Linking relies on ``page.label``, so run ``pagenum::label_pages`` first.
+pub fn parse_toc(book: &Book) -> Vec<TocEntry> {
+    let mut entries: Vec<TocEntry> = book
+        .pages
+        .iter()
+        .enumerate()
+        .flat_map(|(i, p)| parse_toc_page(p, i))
+        .collect();
+    for entry in &mut entries {
+        entry.target = book
+            .pages
+            .iter()
+            .position(|p| p.label == Some(entry.printed));
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::WordCoords;
+
+    fn line(top: u16, words: &[(u16, u16, &str)]) -> Vec<BookWord> {
+        words
+            .iter()
+            .map(|&(x1, x2, t)| {
+                BookWord::new(
+                    WordCoords {
+                        x1,
+                        y1: top,
+                        x2,
+                        y2: top + 40,
+                        base: top + 40,
+                    },
+                    t.to_owned(),
+                )
+            })
+            .collect()
+    }
+
+    fn contents() -> BookPage {
+        let mut page = BookPage::new();
+        page.width = 2000;
+        page.height = 3000;
+        page.dpi = 600;
+        page.lines = vec![
+            line(300, &[(800, 1200, "CONTENTS")]),
+            line(
+                400,
+                &[
+                    (200, 400, "To"),
+                    (420, 500, "a"),
+                    (520, 800, "Skylark"),
+                    (820, 1400, "........"),
+                    (1420, 1600, "Shelley"),
+                    (1750, 1800, "47"),
+                ],
+            ),
+            line(
+                500,
+                &[
+                    (200, 400, "Ode"),
+                    (420, 500, "to"),
+                    (520, 900, "Autumn......"),
+                    (1740, 1800, "52"),
+                ],
+            ),
+            line(
+                600,
+                &[
+                    (200, 400, "The"),
+                    (420, 600, "Rime"),
+                    (620, 700, "of"),
+                    (720, 800, "the"),
+                ],
+            ),
+            line(
+                700,
+                &[
+                    (200, 400, "Ancient"),
+                    (420, 800, "Mariner,"),
+                    (820, 900, "by"),
+                    (920, 1400, "Coleridge"),
+                    (1740, 1800, "60"),
+                ],
+            ),
+            line(
+                800,
+                &[
+                    (200, 400, "Kubla"),
+                    (420, 600, "Khan"),
+                    (620, 800, ". . ."),
+                    (1740, 1800, "91"),
+                ],
+            ),
+        ];
+        page
+    }
+
+    #[test]
+    fn parses_entries() {
+        let entries = parse_toc_page(&contents(), 5);
+        assert_eq!(4, entries.len());
+        assert_eq!("To a Skylark", entries[0].title);
+        assert_eq!(Some("Shelley".to_owned()), entries[0].author);
+        assert_eq!(PageLabel::Arabic(47), entries[0].printed);
+        assert_eq!("Ode to Autumn", entries[1].title);
+        assert_eq!(None, entries[1].author);
+        assert_eq!("The Rime of the Ancient Mariner", entries[2].title);
+        assert_eq!(Some("Coleridge".to_owned()), entries[2].author);
+        assert_eq!("Kubla Khan", entries[3].title);
+        assert_eq!(5, entries[3].toc_page);
+    }
+
+    #[test]
+    fn headings_are_not_titles() {
+        // Put section headings where "The Rime of the" starts the title below it.
+        let headings: &[&[(u16, u16, &str)]] = &[
+            &[(800, 1200, "Sonnets")],
+            &[(200, 350, "PART"), (370, 500, "II")],
+        ];
+        for heading in headings {
+            let mut page = contents();
+            page.lines[3] = line(600, heading);
+            let entries = parse_toc_page(&page, 5);
+            assert_eq!(4, entries.len());
+            assert_eq!("Ancient Mariner", entries[2].title);
+        }
+    }
+
+    #[test]
+    fn leaders() {
+        assert!(is_leader("........"));
+        assert!(is_leader(". . ."));
+        assert!(is_leader("··"));
+        assert!(is_leader("--"));
+        assert!(!is_leader("-"));
+        assert!(!is_leader("."));
+        assert!(!is_leader(""));
+        assert!(!is_leader("..a"));
+        assert_eq!("Autumn", strip_leaders("Autumn......"));
+        assert_eq!("Ode.", strip_leaders("Ode."));
+        assert_eq!("Ode·", strip_leaders("Ode·"));
+        assert_eq!("Ode…", strip_leaders("Ode…"));
+        assert_eq!("Ode", strip_leaders("Ode·…"));
+        assert_eq!("well-", strip_leaders("well-"));
+    }
+
+    #[test]
+    fn links_to_labeled_pages() {
+        let mut book = Book::new();
+        book.pages.push(contents());
+        let mut poem = BookPage::new();
+        poem.label = Some(PageLabel::Arabic(52));
+        book.pages.push(poem);
+        let entries = parse_toc(&book);
+        assert_eq!(None, entries[0].target);
+        assert_eq!(Some(1), entries[1].target);
+    }
+
+    #[test]
+    fn prose_is_not_contents() {
+        let mut page = contents();
+        page.lines.truncate(3);
+        assert!(parse_toc_page(&page, 0).is_empty());
+    }
+}
@@ -1,3 +1,7 @@
+use export::{page_text, TextOptions};
+use meta::BookMetadata;
+use pagenum::PageLabel;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct WordCoords {
     pub x1: u16,
@@ -9,9 +13,16 @@ pub struct WordCoords {
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RichDjVu {
+    StartPage,
     PageDimensions(u32, u32),
     PageDPI(u32),
+    /// Any other ``PARAM`` of the page, e.g. ``PAGE``, as a name and value.
+    PageParam(String, String),
+    /// Any other attribute of the ``OBJECT``, e.g. ``data`` or ``usemap``, as a name and value.
+    PageAttribute(String, String),
     StartLine,
+    /// The ``coords`` of the following word had to be repaired; holds the original attribute.
+    RepairedCoords(String),
     Word(Option<WordCoords>, String),
     EndLine,
     EndPage,
@@ -28,12 +39,47 @@ impl BookWord {
         BookWord { coords, text }
     }
 }
+/// Why a page is (or is not) usable for layout-based features.
+///
+/// Every ``OBJECT`` in the XML becomes a ``BookPage``, so page indices line up with the
+/// physical scan order; this status records what was wrong with the ones that are not ``Ok``.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PageStatus {
+    Ok,
+    /// No lines of text on the page (covers, plates, blank leaves).
+    Blank,
+    /// The ``OBJECT`` had a zero or missing width or height.
+    MissingDims,
+    /// No ``DPI`` parameter was found for the page.
+    MissingDpi,
+    /// The page is usable, but this many words had ``coords`` we could not parse.
+    BadCoords(u32),
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BookPage {
     pub width: u32,
     pub height: u32,
     pub dpi: u32,
     pub lines: Vec<Vec<BookWord>>,
+    /// Number of words dropped from this page because their coordinates were unreadable.
+    pub bad_coords: u32,
+    /// Indices into ``lines`` of running headers (see ``running::mark_running_lines``).
+    #[serde(default)]
+    pub header_lines: Vec<usize>,
+    /// Indices into ``lines`` of running footers.
+    #[serde(default)]
+    pub footer_lines: Vec<usize>,
+    /// The page number printed on this page (see ``pagenum::label_pages``).
+    #[serde(default)]
+    pub label: Option<PageLabel>,
+    /// Attributes of the ``OBJECT`` other than its dimensions, in order, so that
+    /// ``writer::write_djvu_xml`` can put them back.
+    #[serde(default)]
+    pub attributes: Vec<(String, String)>,
+    /// ``PARAM``s of the page other than ``DPI``, in order.
+    #[serde(default)]
+    pub params: Vec<(String, String)>,
 }
 impl BookPage {
     pub fn new() -> Self {
@@ -42,46 +88,110 @@ impl BookPage {
             height: 0,
             dpi: 0,
             lines: Vec::new(),
+            bad_coords: 0,
+            header_lines: Vec::new(),
+            footer_lines: Vec::new(),
+            label: None,
+            attributes: Vec::new(),
+            params: Vec::new(),
+        }
+    }
+    pub fn status(&self) -> PageStatus {
+        if self.width == 0 || self.height == 0 {
+            PageStatus::MissingDims
+        } else if self.dpi == 0 {
+            PageStatus::MissingDpi
+        } else if self.lines.iter().all(|l| l.is_empty()) {
+            PageStatus::Blank
+        } else if self.bad_coords > 0 {
+            PageStatus::BadCoords(self.bad_coords)
+        } else {
+            PageStatus::Ok
         }
     }
     pub fn valid(&self) -> bool {
-        self.width != 0 && self.height != 0 && self.dpi != 0 && !self.lines.is_empty()
+        matches!(self.status(), PageStatus::Ok | PageStatus::BadCoords(_))
+    }
+    /// The text of this page in reading order: words separated by tabs, lines by newlines.
+    ///
+    /// See ``export::page_text`` for other layouts.
+    pub fn text(&self) -> String {
+        page_text(self, &TextOptions::default(), false)
     }
 }
+impl Default for BookPage {
+    fn default() -> Self {
+        BookPage::new()
+    }
+}
+/// Book-wide counts of what happened to damaged word coordinates.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoordsReport {
+    /// Attributes we could read after fixing OCR confusions or stray commas.
+    pub repaired: u32,
+    /// Words placed using their neighbors on the line.
+    pub inferred: u32,
+    /// Words we had to drop because nothing could place them.
+    pub dropped: u32,
+}
+
+/// Where the coordinates of a book's words came from.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Geometry {
+    /// Measured on page images by OCR.
+    #[default]
+    Scanned,
+    /// Made up from plain text (see ``plaintext``): margins, line lengths and spacing follow a
+    /// monospace estimate rather than the printed page, though indentation and blank lines
+    /// still come from the text.
+    Synthetic,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Book {
-    pub pages: Vec<BookPage>
+    pub pages: Vec<BookPage>,
+    pub coords: CoordsReport,
+    #[serde(default)]
+    pub geometry: Geometry,
+    /// From the ``_meta.xml`` next to the book in its Internet Archive item, if there was one.
+    #[serde(default)]
+    pub metadata: Option<BookMetadata>,
 }
 impl Book {
     pub fn new() -> Book {
-        let mut pages = Vec::new();
-        pages.push(BookPage::new());
-        Book { pages }
+        Book {
+            pages: Vec::new(),
+            coords: CoordsReport::default(),
+            geometry: Geometry::Scanned,
+            metadata: None,
+        }
     }
+    /// The page we are currently filling; one is created if the XML has words before any page.
     pub fn current_page(&mut self) -> &mut BookPage {
+        if self.pages.is_empty() {
+            self.start_page();
+        }
         self.pages.last_mut().unwrap()
     }
+    /// The line we are currently filling; one is created if the XML has words outside a line.
     pub fn current_line(&mut self) -> &mut Vec<BookWord> {
-        self.pages.last_mut().unwrap().lines.last_mut().unwrap()
+        let page = self.current_page();
+        if page.lines.is_empty() {
+            page.lines.push(Vec::new());
+        }
+        page.lines.last_mut().unwrap()
     }
-    pub fn end_page(&mut self) {
+    pub fn start_page(&mut self) {
         self.pages.push(BookPage::new())
     }
+    /// The text of a page in reading order: words separated by tabs, lines by newlines.
     pub fn get_page_text(&self, index: usize) -> String {
-        let mut words = String::new();
-        let lines = &self.pages[index].lines;
-
-        for line in lines {
-            for (i, &BookWord{ ref text, .. }) in line.iter().enumerate() {
-                if i > 0 {
-                    words.push('\t')
-                }
-                words.push_str(text)
-            }
-            words.push('\n')
-        }
-
-        words
+        self.pages[index].text()
+    }
+}
+impl Default for Book {
+    fn default() -> Self {
+        Book::new()
     }
 }
 
@@ -0,0 +1,137 @@
+use std::io::{self, Write};
+use types::{Book, BookPage};
+
+/// Escape text for use in XML content or a double-quoted attribute.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn write_page<W: Write>(page: &BookPage, out: &mut W) -> io::Result<()> {
+    write!(out, "<OBJECT")?;
+    for (name, value) in &page.attributes {
+        write!(out, " {}=\"{}\"", name, escape(value))?;
+    }
+    writeln!(out, " height=\"{}\" width=\"{}\">", page.height, page.width)?;
+    for (name, value) in &page.params {
+        writeln!(
+            out,
+            "<PARAM name=\"{}\" value=\"{}\" />",
+            escape(name),
+            escape(value)
+        )?;
+    }
+    if page.dpi > 0 {
+        writeln!(out, "<PARAM name=\"DPI\" value=\"{}\" />", page.dpi)?;
+    }
+    writeln!(out, "<HIDDENTEXT>\n<PAGECOLUMN>\n<REGION>\n<PARAGRAPH>")?;
+    for line in &page.lines {
+        write!(out, "<LINE>")?;
+        for word in line {
+            let c = &word.coords;
+            // The order ``WordCoords`` are read in: x1, y2, x2, y1, base.
+            write!(
+                out,
+                "<WORD coords=\"{},{},{},{},{}\">{}</WORD>",
+                c.x1,
+                c.y2,
+                c.x2,
+                c.y1,
+                c.base,
+                escape(&word.text)
+            )?;
+        }
+        writeln!(out, "</LINE>")?;
+    }
+    writeln!(
+        out,
+        "</PARAGRAPH>\n</REGION>\n</PAGECOLUMN>\n</HIDDENTEXT>\n</OBJECT>"
+    )
+}
+
+/// Write a book as DjVu XML, as the Internet Archive publishes it.
+///
+/// Every page, line and word is written with its dimensions, ``DPI``, other ``PARAM``s and
+/// ``OBJECT`` attributes, so ``process_book`` reads the same book back. What the archive's
+/// XML had but a ``Book`` does not keep is lost: regions and paragraphs (each page becomes one
+/// of each), image maps, and words dropped for unreadable coordinates. Running headers and
+/// page labels are not written; they are found again from the text.
+pub fn write_djvu_xml<W: Write>(book: &Book, out: &mut W) -> io::Result<()> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        out,
+        "<!DOCTYPE DjVuXML PUBLIC \"-//W3C//DTD DjVuXML 1.1//EN\" \"pubtext/DjVuXML-s.dtd\">"
+    )?;
+    writeln!(out, "<DjVuXML>\n<HEAD></HEAD>\n<BODY>")?;
+    for page in &book.pages {
+        write_page(page, out)?;
+    }
+    writeln!(out, "</BODY>\n</DjVuXML>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::RichDjVu;
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<DjVuXML><BODY>
+<OBJECT data="file://localhost//tmp/book.djvu" height="3000" type="image/x.djvu" usemap="book_0001.djvu" width="2000">
+<PARAM name="PAGE" value="book_0001.djvu"/><PARAM name="DPI" value="600"/>
+<HIDDENTEXT><PAGECOLUMN><REGION><PARAGRAPH>
+<LINE><WORD coords="814,1248,1012,1168,1247">Tom &amp; Jerry</WORD><WORD coords="1030,1250,1300,1170">&lt;said&gt;</WORD></LINE>
+<LINE></LINE>
+</PARAGRAPH></REGION></PAGECOLUMN></HIDDENTEXT></OBJECT>
+<OBJECT height="3000" width="2000"><HIDDENTEXT/></OBJECT>
+</BODY></DjVuXML>"#;
+
+    fn rich_events(xml: &[u8]) -> Vec<RichDjVu> {
+        let mut events = Vec::new();
+        ::parse_rich_xml(xml, |e| events.push(e));
+        events
+    }
+
+    #[test]
+    fn round_trip() {
+        let book = ::process_book(XML.as_bytes()).unwrap();
+        assert_eq!(
+            vec![("PAGE".to_owned(), "book_0001.djvu".to_owned())],
+            book.pages[0].params
+        );
+        assert_eq!(3, book.pages[0].attributes.len());
+
+        let mut written = Vec::new();
+        write_djvu_xml(&book, &mut written).unwrap();
+        assert_eq!(book, ::process_book(&written[..]).unwrap());
+        assert_eq!(rich_events(XML.as_bytes()), rich_events(&written));
+    }
+
+    #[test]
+    fn subset() {
+        let mut book = ::process_book(XML.as_bytes()).unwrap();
+        book.pages.remove(0);
+        let mut written = Vec::new();
+        write_djvu_xml(&book, &mut written).unwrap();
+        let reread = ::process_book(&written[..]).unwrap();
+        assert_eq!(1, reread.pages.len());
+        assert_eq!(0, reread.pages[0].dpi);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            "&lt;a href=&quot;x&quot;&gt;&amp;",
+            escape("<a href=\"x\">&")
+        );
+    }
+}
@@ -1,3 +1,4 @@
+import argparse
 import sklearn
 import numpy as np
 import json
@@ -10,6 +11,13 @@ from sklearn.metrics import roc_auc_score, precision_score, recall_score
 from sklearn.tree import _tree
 from collections import defaultdict
 
+parser = argparse.ArgumentParser(description="Train the poetry page classifier.")
+parser.add_argument("--truth", default="truth-data/truth.jsonl", help="Labeled page features.")
+parser.add_argument("--output", default="forest.json", help="Where to write the model.")
+# The classification --stopwords setting used to extract the truth features.
+parser.add_argument("--stopwords", default="inquery")
+args = parser.parse_args()
+
 # If it doesn't have the POETRY label, it's not.
 # Need numeric labels for learning.
 def label_to_y(label):
@@ -38,7 +46,7 @@ ys = []
 books = []
 pages = []
 
-with open("truth-data/truth.jsonl") as fp:
+with open(args.truth) as fp:
     for line in fp:
         instance = json.loads(line)
         data.append(instance["features"])
@@ -115,10 +123,12 @@ def dump_tree(tree_model):
 # Saving the feature names allows us to tell if this model is out of date
 forest = {
     "feature_names": fnames,
+    # The stopword list the truth-data features were extracted with (--stopwords).
+    "stopwords": args.stopwords,
     "forest": [[dump_tree(e) for e in m.estimators_] for m in models],
 }
 
 # Actually save the data here:
-with open("forest.json", "w") as fp:
+with open(args.output, "w") as fp:
     json.dump(forest, fp)
 